@@ -0,0 +1,154 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote, Data, DeriveInput, Error, Fields, GenericArgument, GenericParam, Ident, Lit, Meta,
+    NestedMeta, PathArguments, Type,
+};
+
+use lrpmp_spec::codegen::{field_count, gen_message_codec, MsgField};
+
+struct KindAttr {
+    name: String,
+    code: u8,
+}
+
+pub fn derive_message(input: DeriveInput) -> TokenStream {
+    match try_derive_message(input) {
+        Ok(tokens) => tokens,
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn try_derive_message(input: DeriveInput) -> Result<TokenStream, Error> {
+    let kind = parse_kind_attr(&input)?;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input,
+                    "expected a struct with named fields",
+                ))
+            }
+        },
+        _ => return Err(Error::new_spanned(&input, "expected a struct")),
+    };
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|f| {
+            let (ty, optional) = match option_inner_ty(&f.ty) {
+                Some(ty) => (ty, true),
+                None => (&f.ty, false),
+            };
+            MsgField {
+                ident: f.ident.clone().unwrap(),
+                ty: quote!(#ty),
+                optional,
+            }
+        })
+        .collect();
+    if let Some(pos) = fields.iter().position(|f| f.optional) {
+        if let Some(f) = fields[pos..].iter().find(|f| !f.optional) {
+            return Err(Error::new_spanned(
+                &f.ident,
+                "required fields can't follow optional fields",
+            ));
+        }
+    }
+    let (fields_min, fields_max) = field_count(&fields);
+
+    let struct_ident = &input.ident;
+    let kind_name = &kind.name;
+    let kind_code = kind.code;
+
+    // The message is generic over the map and value types unless the struct
+    // already declares them.
+    let mut generics = input.generics.clone();
+    for param in &["M", "V"] {
+        let param = Ident::new(param, struct_ident.span());
+        let declared = generics.type_params().any(|p| p.ident == param);
+        if !declared {
+            generics
+                .params
+                .push(GenericParam::Type(parse_quote!(#param)));
+        }
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let kind = quote!(::lrpmp::types::KnownKind::Custom(
+        <Self as ::lrpmp::message::CustomMessage<M, V>>::KIND
+    ));
+    let codec_fns = gen_message_codec(kind.clone(), &fields, quote!());
+
+    Ok(quote!(
+        impl #impl_generics ::lrpmp::message::CustomMessage<M, V> for #struct_ident #ty_generics #where_clause {
+            const KIND: ::lrpmp::types::CustomKind = ::lrpmp::types::CustomKind::new(
+                #kind_name,
+                #kind_code,
+                #fields_min,
+                Some(#fields_max),
+            );
+        }
+
+        impl #impl_generics ::lrpmp::message::Message<M, V> for #struct_ident #ty_generics #where_clause {
+            fn kind(&self) -> ::lrpmp::types::KnownKind {
+                #kind
+            }
+
+            #codec_fns
+        }
+    ))
+}
+
+fn parse_kind_attr(input: &DeriveInput) -> Result<KindAttr, Error> {
+    let attr = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("lrpmp"))
+        .ok_or_else(|| {
+            Error::new_spanned(
+                &input.ident,
+                "expected `#[lrpmp(kind = \"...\", code = ...)]` attribute",
+            )
+        })?;
+    let list = match attr.parse_meta()? {
+        Meta::List(list) => list,
+        meta => return Err(Error::new_spanned(meta, "expected `lrpmp(...)`")),
+    };
+    let mut name = None;
+    let mut code = None;
+    for nested in list.nested.iter() {
+        let pair = match nested {
+            NestedMeta::Meta(Meta::NameValue(pair)) => pair,
+            other => return Err(Error::new_spanned(other, "expected `key = value`")),
+        };
+        match &pair.lit {
+            Lit::Str(s) if pair.path.is_ident("kind") => name = Some(s.value()),
+            Lit::Int(i) if pair.path.is_ident("code") => code = Some(i.base10_parse()?),
+            _ => return Err(Error::new_spanned(pair, "unknown attribute")),
+        }
+    }
+    match (name, code) {
+        (Some(name), Some(code)) => Ok(KindAttr { name, code }),
+        _ => Err(Error::new_spanned(attr, "expected both `kind` and `code`")),
+    }
+}
+
+/// Returns `T` given `Option<T>`.
+fn option_inner_ty(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
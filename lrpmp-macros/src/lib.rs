@@ -1,17 +1,29 @@
 extern crate proc_macro;
 
+mod derive;
 mod imp;
 mod spec;
 
 use proc_macro::TokenStream;
 use proc_macro_hack::proc_macro_hack;
-use syn::{parse_macro_input, LitStr};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, DeriveInput, LitStr, Token};
 
+use self::derive::derive_message as inner_derive_message;
+use self::imp::impl_spec_version as inner_impl_spec_version;
 use self::imp::impl_std_kind as inner_impl_std_kind;
 use self::imp::impl_std_messages as inner_impl_std_messages;
 use self::imp::impl_std_uris as inner_impl_std_uris;
 use self::imp::impl_uri as inner_impl_uri;
 
+#[proc_macro]
+pub fn impl_spec_version(tokens: TokenStream) -> TokenStream {
+    let spec_path = parse_macro_input!(tokens as Option<LitStr>);
+    let spec_path = spec_path.map(|lit_str| lit_str.value());
+
+    inner_impl_spec_version(spec_path).into()
+}
+
 #[proc_macro]
 pub fn impl_std_kind(tokens: TokenStream) -> TokenStream {
     let spec_path = parse_macro_input!(tokens as Option<LitStr>);
@@ -38,7 +50,15 @@ pub fn impl_std_uris(tokens: TokenStream) -> TokenStream {
 
 #[proc_macro_hack]
 pub fn uri(tokens: TokenStream) -> TokenStream {
-    let uri_str_lit = parse_macro_input!(tokens as LitStr);
+    let uri_str_lits =
+        parse_macro_input!(tokens with Punctuated::<LitStr, Token![,]>::parse_terminated);
+
+    inner_impl_uri(uri_str_lits.into_iter().collect()).into()
+}
+
+#[proc_macro_derive(Message, attributes(lrpmp))]
+pub fn derive_message(tokens: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(tokens as DeriveInput);
 
-    inner_impl_uri(uri_str_lit).into()
+    inner_derive_message(input).into()
 }
@@ -0,0 +1,34 @@
+//! Prints the documentation of a spec.
+//!
+//! Usage: `lrpmp-docs <markdown|html> [SPEC_PATH]`
+//!
+//! The current spec is used if no path is given.
+use std::env;
+use std::process;
+
+use lrpmp_spec::docs::{render, DocFormat};
+use lrpmp_spec::{Error, Spec};
+
+fn run(args: &[String]) -> Result<String, Error> {
+    let format: DocFormat = args[0].parse()?;
+    let spec = match args.get(1) {
+        Some(path) => Spec::load(path)?,
+        None => Spec::current()?,
+    };
+    Ok(render(&spec.validate()?, format))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.len() > 2 {
+        eprintln!("usage: lrpmp-docs <markdown|html> [SPEC_PATH]");
+        process::exit(2);
+    }
+    match run(&args) {
+        Ok(docs) => print!("{}", docs),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
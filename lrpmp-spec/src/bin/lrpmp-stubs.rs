@@ -0,0 +1,35 @@
+//! Prints client stubs for a spec.
+//!
+//! Usage: `lrpmp-stubs <typescript|python> [SPEC_PATH]`
+//!
+//! The current spec is used if no path is given.
+use std::env;
+use std::process;
+
+use lrpmp_spec::stubs::{generate, Language};
+use lrpmp_spec::{Error, Spec};
+
+fn run(args: &[String]) -> Result<String, Error> {
+    let language: Language = args[0].parse()?;
+    let spec = match args.get(1) {
+        Some(path) => Spec::load(path)?,
+        None => Spec::current()?,
+    };
+    let spec = spec.rename(language.naming()).validate()?;
+    Ok(generate(&spec, language))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.len() > 2 {
+        eprintln!("usage: lrpmp-stubs <typescript|python> [SPEC_PATH]");
+        process::exit(2);
+    }
+    match run(&args) {
+        Ok(stubs) => print!("{}", stubs),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Rust code generation from a spec.
+//!
+//! The `impl_*!` macros of `lrpmp-macros` expand to this code, and a
+//! [`Generator`] writes the same code to a file from a build script.
+//!
+//! The generated code refers to `lrpmp` types by their bare names, so the
+//! module it is included in must import them:
+//!
+//! ```ignore
+//! pub mod generated {
+//!     use lrpmp::message::dec::*;
+//!     use lrpmp::message::enc::*;
+//!     use lrpmp::message::*;
+//!     use lrpmp::types::*;
+//!
+//!     include!(concat!(env!("OUT_DIR"), "/lrpmp.rs"));
+//! }
+//! ```
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+
+use crate::errors::ErrorKind;
+use crate::naming::RUST_NAMING_CONVENTION;
+use crate::{uri, Error, MsgDef, Spec};
+
+/// Writes the code generated from a spec to a file.
+///
+/// # Example
+/// ```ignore
+/// // build.rs
+/// use lrpmp_spec::codegen::Generator;
+/// use lrpmp_spec::Spec;
+///
+/// fn main() {
+///     println!("cargo:rerun-if-changed=spec.toml");
+///     let spec = Spec::load("spec.toml").unwrap();
+///     Generator::new(spec)
+///         .unwrap()
+///         .write_to_out_dir("lrpmp.rs")
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Generator {
+    spec: Spec,
+    kind: bool,
+    messages: bool,
+    uris: bool,
+}
+
+impl Generator {
+    /// Renames the spec to the Rust naming convention and validates it.
+    ///
+    /// All items are generated by default.
+    pub fn new(spec: Spec) -> Result<Self, Error> {
+        let spec = spec.rename(RUST_NAMING_CONVENTION).validate()?;
+        Ok(Self {
+            spec,
+            kind: true,
+            messages: true,
+            uris: true,
+        })
+    }
+
+    /// Sets whether to generate the `StandardKind` enum.
+    pub fn kind(mut self, enabled: bool) -> Self {
+        self.kind = enabled;
+        self
+    }
+
+    /// Sets whether to generate the message structs and `StandardMessage`.
+    pub fn messages(mut self, enabled: bool) -> Self {
+        self.messages = enabled;
+        self
+    }
+
+    /// Sets whether to generate the `*_URI` statics.
+    pub fn uris(mut self, enabled: bool) -> Self {
+        self.uris = enabled;
+        self
+    }
+
+    pub fn generate(&self) -> TokenStream {
+        let mut out = gen_spec_version(&self.spec);
+        if self.kind {
+            out.extend(gen_std_kind(&self.spec));
+        }
+        if self.messages {
+            out.extend(gen_std_messages(&self.spec));
+        }
+        if self.uris {
+            out.extend(gen_std_uris(&self.spec));
+        }
+        out
+    }
+
+    /// Returns the generated source, formatted with `rustfmt` if available.
+    pub fn generate_source(&self) -> String {
+        let code = self.generate().to_string();
+        let code = rustfmt(&code).unwrap_or(code);
+        format!(
+            "// Generated by lrpmp-spec from spec version {}. Do not edit.\n\n{}",
+            self.spec.version(),
+            code
+        )
+    }
+
+    pub fn write<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.generate_source())?;
+        Ok(())
+    }
+
+    /// Writes to `file_name` within `OUT_DIR`, returning the full path.
+    pub fn write_to_out_dir<P>(&self, file_name: P) -> Result<PathBuf, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let out_dir = env::var_os("OUT_DIR").ok_or(ErrorKind::NoOutDir)?;
+        let path = PathBuf::from(out_dir).join(file_name);
+        self.write(&path)?;
+        Ok(path)
+    }
+}
+
+/// Formats code with the `rustfmt` given by `RUSTFMT`, or the one in `PATH`.
+fn rustfmt(code: &str) -> Option<String> {
+    let rustfmt = env::var_os("RUSTFMT").unwrap_or_else(|| "rustfmt".into());
+    let mut child = Command::new(rustfmt)
+        .args(["--edition", "2018", "--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(code.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Generates the `SPEC_VERSION` constant.
+pub fn gen_spec_version(spec: &Spec) -> TokenStream {
+    let version = spec.version().to_string();
+    quote!(
+        /// The version of the spec the code was generated from.
+        pub const SPEC_VERSION: &str = #version;
+    )
+}
+
+/// Generates the `*_URI` statics.
+pub fn gen_std_uris(spec: &Spec) -> TokenStream {
+    let mut out = TokenStream::new();
+
+    for uri_def in spec.uri_iter() {
+        let name = ident(format!("{}_URI", uri_def.name()));
+        let uri_expr = match gen_uri(uri_def.uri()) {
+            Ok(uri_expr) => uri_expr,
+            Err(err) => {
+                let message = err.message_with_uri(uri_def.uri());
+                quote!(compile_error!(#message))
+            }
+        };
+
+        out.extend(quote!(
+            pub static #name: Uri = #uri_expr;
+        ));
+    }
+
+    out
+}
+
+/// Generates an expression constructing a `Uri` from a validated str.
+pub fn gen_uri(uri_str: &str) -> Result<TokenStream, uri::UriValidationError> {
+    let uri_parts = uri::validate_bytes(uri_str.as_bytes())?;
+    Ok(quote!(unsafe { Uri::from_static_parts_unchecked(#uri_str, #uri_parts) }))
+}
+
+/// Generates the message structs and the `StandardMessage` enum.
+pub fn gen_std_messages(spec: &Spec) -> TokenStream {
+    let mut out = TokenStream::new();
+
+    for msg in spec.message_iter() {
+        out.extend(gen_message(msg));
+    }
+
+    out.extend(gen_std_message(spec));
+    out
+}
+
+fn gen_std_message(spec: &Spec) -> TokenStream {
+    let kind_idents: Vec<_> = spec.message_iter().map(msg_kind_ident).collect();
+    let message_idents: Vec<_> = spec.message_iter().map(msg_struct_ident).collect();
+
+    quote!(
+        /// Enum of all standard messages.
+        #[derive(Debug, Clone)]
+        pub enum StandardMessage<M, V> {
+            #(#kind_idents(#message_idents<M, V>)),*
+        }
+
+        impl<M, V> Message<M, V> for StandardMessage<M, V> {
+            fn kind(&self) -> KnownKind {
+                match self {
+                    #(Self::#kind_idents(m) => m.kind()),*
+                }
+            }
+
+            fn encode<E>(self, encoder: E) -> Result<E::Ok, MessageError<E::Error>>
+            where
+                E: MessageEncoder<M, V>
+            {
+                match self {
+                    #(Self::#kind_idents(m) => m.encode(encoder)),*
+                }
+            }
+
+            fn encode_ref<E>(&self, encoder: E) -> Result<E::Ok, MessageError<E::Error>>
+            where
+                E: MessageEncoder<M, V>
+            {
+                match self {
+                    #(Self::#kind_idents(m) => m.encode_ref(encoder)),*
+                }
+            }
+
+            fn decode<D>(decoder: D) -> Result<Self, MessageError<D::Error>>
+            where
+                D: MessageDecoder<M, V>
+            {
+                let (kind, decoder) = decoder.start()?;
+                let decoder = KindDecoder::new(kind, decoder);
+                let std_kind = match kind {
+                    k @ KnownKind::Custom(_) => {
+                        return Err(MessageError::UnexpectedKind(Kind::Known(k)).into())
+                    },
+                    KnownKind::Standard(k) => k,
+                };
+
+                let message = match std_kind {
+                    #(
+                        StandardKind::#kind_idents => StandardMessage::#kind_idents(#message_idents::decode(decoder)?)
+                    ),*
+                };
+
+                Ok(message)
+            }
+
+            fn into_standard(self) -> Result<Self, MessageError<()>> {
+                Ok(self)
+            }
+        }
+    )
+}
+
+/// Generates the `StandardKind` enum.
+pub fn gen_std_kind(spec: &Spec) -> TokenStream {
+    let (kind_fields_min, kind_fields_max): (Vec<_>, Vec<_>) = spec
+        .message_iter()
+        .map(|m| field_count(&msg_fields(m)))
+        .unzip();
+    let kind_names: Vec<_> = spec.message_iter().map(|m| m.kind_name()).collect();
+    let kind_idents: Vec<_> = spec.message_iter().map(msg_kind_ident).collect();
+    let kind_codes: Vec<_> = spec.message_iter().map(|m| m.kind_code()).collect();
+    let kind_stages: Vec<_> = spec
+        .message_iter()
+        .map(|m| {
+            let stages = m.stages();
+            quote!(&[#(#stages),*])
+        })
+        .collect();
+
+    quote!(
+        /// Standard defined message kinds.
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        #[repr(u8)]
+        pub enum StandardKind {
+            #(
+                #kind_idents = #kind_codes
+            ),*
+        }
+
+        impl StandardKind {
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    #(#kind_names => Some(Self::#kind_idents)),*,
+                    _ => None,
+                }
+            }
+
+            pub fn from_code(code: u8) -> Option<Self> {
+                match code {
+                    #(#kind_codes => Some(Self::#kind_idents)),*,
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    #(Self::#kind_idents => #kind_names),*
+                }
+            }
+
+            /// Returns the lower and upper bound of the number of fields in the message kind.
+            pub fn field_count(&self) -> (usize, Option<usize>) {
+                match self {
+                    #(Self::#kind_idents => (#kind_fields_min, Some(#kind_fields_max))),*
+                }
+            }
+
+            /// Returns the names of the session stages the message kind is valid in.
+            pub fn stages(self) -> &'static [&'static str] {
+                match self {
+                    #(Self::#kind_idents => #kind_stages),*
+                }
+            }
+        }
+    )
+}
+
+fn gen_message(def: &MsgDef) -> TokenStream {
+    let kind_ident = msg_kind_ident(def);
+    let struct_ident = msg_struct_ident(def);
+    let struct_doc = def.desc();
+    let fields = msg_fields(def);
+    let field_idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+    let field_types: Vec<_> = fields.iter().map(MsgField::decl_ty).collect();
+    let codec_fns = gen_message_codec(
+        quote!(KnownKind::Standard(StandardKind::#kind_ident)),
+        &fields,
+        quote!(_seal: (),),
+    );
+
+    quote!(
+        #[derive(Debug, Clone)]
+        #[doc = #struct_doc]
+        pub struct #struct_ident<M, V> {
+            #(pub #field_idents: #field_types),*,
+            // Only allow construction via public methods.
+            _seal: (),
+        }
+
+        impl<M, V> #struct_ident<M, V> {
+            pub fn new(
+                #(#field_idents: #field_types),*,
+            ) -> Self {
+                Self {
+                    #(#field_idents),*,
+                    _seal: (),
+                }
+            }
+        }
+
+        impl<M, V> Message<M, V> for #struct_ident<M, V> {
+            fn kind(&self) -> KnownKind {
+                KnownKind::Standard(StandardKind::#kind_ident)
+            }
+
+            #codec_fns
+
+            fn into_standard(self) -> Result<StandardMessage<M, V>, MessageError<()>> {
+                Ok(StandardMessage::#kind_ident(self))
+            }
+        }
+    )
+}
+
+/// A field of a generated message.
+pub struct MsgField {
+    pub ident: Ident,
+    /// The field type, without the `Option` of an optional field.
+    pub ty: TokenStream,
+    pub optional: bool,
+}
+
+impl MsgField {
+    /// Returns the type the field is declared with.
+    pub fn decl_ty(&self) -> TokenStream {
+        let ty = &self.ty;
+        if self.optional {
+            quote!(Option<#ty>)
+        } else {
+            ty.clone()
+        }
+    }
+}
+
+/// Returns the lower and upper bound of the number of fields.
+pub fn field_count(fields: &[MsgField]) -> (usize, usize) {
+    let required = fields.iter().filter(|f| !f.optional).count();
+    (required, fields.len())
+}
+
+/// Generates the `encode`, `encode_ref` and `decode` methods of a message.
+///
+/// Optional fields are trailing and left off the wire when missing. An
+/// optional field can't be encoded if one before it is missing.
+pub fn gen_message_codec(
+    kind: TokenStream,
+    fields: &[MsgField],
+    extra_init: TokenStream,
+) -> TokenStream {
+    let encode_fields = gen_encode_fields(fields, false);
+    let encode_ref_fields = gen_encode_fields(fields, true);
+    let decode_fields = fields.iter().map(|f| {
+        let MsgField { ident, ty, .. } = f;
+        let decode = quote!(decoder.decode_field::<#ty>(Some(stringify!(#ident))));
+        if f.optional {
+            quote!(#ident: match #decode {
+                Ok(value) => Some(value),
+                Err(::lrpmp::message::MessageError::Eof) => None,
+                Err(err) => return Err(err),
+            },)
+        } else {
+            quote!(#ident: #decode?,)
+        }
+    });
+
+    quote!(
+        fn encode<E>(self, encoder: E) -> Result<E::Ok, ::lrpmp::message::MessageError<E::Error>>
+        where
+            E: ::lrpmp::message::MessageEncoder<M, V>,
+        {
+            use ::lrpmp::message::enc::MessageFieldEncoder as _;
+            let mut encoder = encoder.start(self.kind())?;
+            #encode_fields
+            encoder.end()
+        }
+
+        fn encode_ref<E>(&self, encoder: E) -> Result<E::Ok, ::lrpmp::message::MessageError<E::Error>>
+        where
+            E: ::lrpmp::message::MessageEncoder<M, V>,
+        {
+            use ::lrpmp::message::enc::MessageFieldEncoder as _;
+            let mut encoder = encoder.start(self.kind())?;
+            #encode_ref_fields
+            encoder.end()
+        }
+
+        fn decode<D>(decoder: D) -> Result<Self, ::lrpmp::message::MessageError<D::Error>>
+        where
+            D: ::lrpmp::message::MessageDecoder<M, V>
+        {
+            use ::lrpmp::message::dec::MessageFieldDecoder as _;
+            let (kind, mut decoder) = decoder.start()?;
+            if kind != #kind {
+                return Err(::lrpmp::message::MessageError::UnexpectedKind(
+                    ::lrpmp::types::Kind::Known(kind)
+                ));
+            }
+            Ok(Self {
+                #(#decode_fields)*
+                #extra_init
+            })
+        }
+    )
+}
+
+fn gen_encode_fields(fields: &[MsgField], by_ref: bool) -> TokenStream {
+    let (method, borrow) = if by_ref {
+        (quote!(encode_field_ref), quote!(&))
+    } else {
+        (quote!(encode_field), quote!())
+    };
+    let optional_count = fields.iter().filter(|f| f.optional).count();
+    let mut out = TokenStream::new();
+    if optional_count > 1 {
+        out.extend(quote!(let mut missing = false;));
+    }
+    let mut optional_index = 0;
+    for f in fields {
+        let ident = &f.ident;
+        if !f.optional {
+            out.extend(quote!(encoder.#method(Some(stringify!(#ident)), #borrow self.#ident)?;));
+            continue;
+        }
+        let check_missing = if optional_index > 0 {
+            quote!(if missing {
+                return Err(::lrpmp::message::MessageError::Custom(
+                    "optional field present after a missing one",
+                ));
+            })
+        } else {
+            quote!()
+        };
+        let set_missing = if optional_index + 1 < optional_count {
+            quote!(missing = true)
+        } else {
+            quote!(())
+        };
+        out.extend(quote!(
+            match #borrow self.#ident {
+                Some(value) => {
+                    #check_missing
+                    encoder.#method(Some(stringify!(#ident)), value)?;
+                }
+                None => #set_missing,
+            }
+        ));
+        optional_index += 1;
+    }
+    out
+}
+
+fn msg_kind_ident(def: &MsgDef) -> Ident {
+    ident(def.name())
+}
+
+fn msg_struct_ident(def: &MsgDef) -> Ident {
+    ident(format!("{}Message", def.name()))
+}
+
+fn msg_fields(def: &MsgDef) -> Vec<MsgField> {
+    def.field_iter()
+        .map(|f| MsgField {
+            ident: ident(f.name()),
+            ty: map_msg_ty(f.ty()),
+            optional: f.is_optional(),
+        })
+        .collect()
+}
+
+fn map_msg_ty<S: AsRef<str>>(ty: S) -> TokenStream {
+    let ty = ty.as_ref();
+    match ty {
+        "Id" => quote!(Id),
+        "Uri" => quote!(Uri),
+        "Kind" => quote!(Kind),
+        "Meta" => quote!(Meta<M, V>),
+        "Body" => quote!(Body<V>),
+        _ => panic!("unknown type: {}", ty),
+    }
+}
+
+fn ident<S>(ident: S) -> Ident
+where
+    S: AsRef<str>,
+{
+    Ident::new(ident.as_ref(), Span::call_site())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_source() {
+        let generator = Generator::new(Spec::current().unwrap()).unwrap();
+        let source = generator.uris(false).generate_source();
+        assert!(source.starts_with("// Generated by lrpmp-spec"));
+        assert!(source.contains("pub enum StandardKind"));
+        assert!(source.contains("pub enum StandardMessage"));
+        assert!(!source.contains("_URI"));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use semver::Version;
+
+use super::{Definition, MsgDef, Spec};
+
+/// How a definition changed between two specs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// The definition kept its code or position but changed name.
+    Renamed {
+        from: String,
+    },
+    TypeChanged {
+        from: String,
+        to: String,
+    },
+    OptionalChanged {
+        optional: bool,
+    },
+    StagesChanged {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Added => f.write_str("added"),
+            Self::Removed => f.write_str("removed"),
+            Self::Renamed { from } => write!(f, "renamed from `{}`", from),
+            Self::TypeChanged { from, to } => write!(f, "type changed from `{}` to `{}`", from, to),
+            Self::OptionalChanged { optional: true } => f.write_str("made optional"),
+            Self::OptionalChanged { optional: false } => f.write_str("made required"),
+            Self::StagesChanged { added, removed } => write!(
+                f,
+                "stages changed (added [{}], removed [{}])",
+                added.join(", "),
+                removed.join(", ")
+            ),
+        }
+    }
+}
+
+/// A change to a definition, and whether peers on the new side of it can
+/// still read what peers on the old side send.
+///
+/// Additions the old side can't read aren't breaking, see
+/// [`Spec::check_compatible`](crate::Spec::check_compatible).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub definition: Definition,
+    pub kind: ChangeKind,
+    pub breaking: bool,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let class = if self.breaking {
+            "breaking"
+        } else {
+            "compatible"
+        };
+        write!(f, "{}: {} ({})", self.definition, self.kind, class)
+    }
+}
+
+/// The changes from one spec to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecDiff {
+    pub from: Version,
+    pub to: Version,
+    pub changes: Vec<Change>,
+}
+
+impl SpecDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|c| c.breaking)
+    }
+
+    pub fn breaking(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|c| c.breaking)
+    }
+}
+
+pub fn run(from: &Spec, to: &Spec) -> SpecDiff {
+    let mut differ = Differ::default();
+    differ.diff_messages(from, to);
+    differ.diff_uris(from, to);
+    SpecDiff {
+        from: from.version().clone(),
+        to: to.version().clone(),
+        changes: differ.changes,
+    }
+}
+
+#[derive(Default)]
+struct Differ {
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn report(&mut self, definition: Definition, kind: ChangeKind, breaking: bool) {
+        self.changes.push(Change {
+            definition,
+            kind,
+            breaking,
+        });
+    }
+
+    /// Messages are matched by code, as that is what goes on the wire.
+    fn diff_messages(&mut self, from: &Spec, to: &Spec) {
+        let to_msgs: HashMap<_, _> = to.message_iter().map(|m| (m.kind_code(), m)).collect();
+        for from_msg in from.message_iter() {
+            match to_msgs.get(&from_msg.kind_code()) {
+                Some(to_msg) => self.diff_message(from_msg, to_msg),
+                None => self.report(message_definition(from_msg), ChangeKind::Removed, true),
+            }
+        }
+        for to_msg in to.message_iter() {
+            let code = to_msg.kind_code();
+            if !from.message_iter().any(|m| m.kind_code() == code) {
+                self.report(message_definition(to_msg), ChangeKind::Added, false);
+            }
+        }
+    }
+
+    fn diff_message(&mut self, from: &MsgDef, to: &MsgDef) {
+        let definition = message_definition(to);
+        if from.kind_name() != to.kind_name() {
+            // Kinds may be sent by name.
+            let from_name = from.kind_name().to_string();
+            self.report(
+                definition.clone(),
+                ChangeKind::Renamed { from: from_name },
+                true,
+            );
+        }
+        let added: Vec<_> = difference(to.stages(), from.stages());
+        let removed: Vec<_> = difference(from.stages(), to.stages());
+        if !added.is_empty() || !removed.is_empty() {
+            // A peer may send the message in a stage the other no longer allows.
+            let breaking = !removed.is_empty();
+            self.report(
+                definition,
+                ChangeKind::StagesChanged { added, removed },
+                breaking,
+            );
+        }
+        // Fields are positional, so are matched by index.
+        let from_fields: Vec<_> = from.field_iter().collect();
+        let to_fields: Vec<_> = to.field_iter().collect();
+        for i in 0..from_fields.len().max(to_fields.len()) {
+            match (from_fields.get(i), to_fields.get(i)) {
+                (Some(from_field), Some(to_field)) => {
+                    let definition = field_definition(to, to_field.name());
+                    if from_field.ty() != to_field.ty() {
+                        let kind = ChangeKind::TypeChanged {
+                            from: from_field.ty().to_string(),
+                            to: to_field.ty().to_string(),
+                        };
+                        self.report(definition.clone(), kind, true);
+                    }
+                    if from_field.name() != to_field.name() {
+                        let kind = ChangeKind::Renamed {
+                            from: from_field.name().to_string(),
+                        };
+                        self.report(definition.clone(), kind, false);
+                    }
+                    if from_field.is_optional() != to_field.is_optional() {
+                        let kind = ChangeKind::OptionalChanged {
+                            optional: to_field.is_optional(),
+                        };
+                        self.report(definition, kind, true);
+                    }
+                }
+                (None, Some(to_field)) => {
+                    // Older peers can still leave an optional field off, and
+                    // are expected to upgrade before being sent it.
+                    let breaking = !to_field.is_optional();
+                    let definition = field_definition(to, to_field.name());
+                    self.report(definition, ChangeKind::Added, breaking);
+                }
+                (Some(from_field), None) => {
+                    let definition = field_definition(from, from_field.name());
+                    self.report(definition, ChangeKind::Removed, true);
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn diff_uris(&mut self, from: &Spec, to: &Spec) {
+        for from_uri in from.uri_iter() {
+            if !to.uri_iter().any(|u| u.uri() == from_uri.uri()) {
+                let definition = Definition::Uri {
+                    uri: from_uri.uri().to_string(),
+                };
+                self.report(definition, ChangeKind::Removed, true);
+            }
+        }
+        for to_uri in to.uri_iter() {
+            if !from.uri_iter().any(|u| u.uri() == to_uri.uri()) {
+                let definition = Definition::Uri {
+                    uri: to_uri.uri().to_string(),
+                };
+                self.report(definition, ChangeKind::Added, false);
+            }
+        }
+    }
+}
+
+fn message_definition(msg: &MsgDef) -> Definition {
+    Definition::Message {
+        code: msg.kind_code(),
+        name: msg.kind_name().to_string(),
+    }
+}
+
+fn field_definition(msg: &MsgDef, name: &str) -> Definition {
+    Definition::Field {
+        message: msg.kind_name().to_string(),
+        name: name.to_string(),
+    }
+}
+
+/// Returns the items of `a` not in `b`.
+fn difference(a: &[String], b: &[String]) -> Vec<String> {
+    a.iter().filter(|s| !b.contains(s)).cloned().collect()
+}
@@ -0,0 +1,275 @@
+//! Protocol documentation generation.
+//!
+//! Renders a table of the message kinds, a section per message describing
+//! its fields, and the catalogue of defined URIs.
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::errors::ErrorKind;
+use crate::{Error, MsgDef, Spec};
+
+/// A format documentation can be rendered to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl FromStr for DocFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(ErrorKind::UnknownDocFormat(s.to_string()).into()),
+        }
+    }
+}
+
+/// Renders the documentation of a spec.
+///
+/// Message kinds are named as in the spec, while field names follow the
+/// naming convention of `spec`.
+pub fn render(spec: &Spec, format: DocFormat) -> String {
+    let mut out = String::new();
+    let result = match format {
+        DocFormat::Markdown => render_markdown(spec, &mut out),
+        DocFormat::Html => render_html(spec, &mut out),
+    };
+    // Writing to a `String` can't fail.
+    result.unwrap();
+    out
+}
+
+fn render_markdown(spec: &Spec, out: &mut String) -> fmt::Result {
+    writeln!(out, "# LRPM Protocol {}", spec.version())?;
+    writeln!(out)?;
+    writeln!(out, "## Messages")?;
+    writeln!(out)?;
+    writeln!(out, "| Code | Name | Type | Stages | Fields |")?;
+    writeln!(out, "| ---: | ---- | ---- | ------ | ------ |")?;
+    for msg in spec.message_iter() {
+        let fields: Vec<_> = msg
+            .field_iter()
+            .map(|f| {
+                let optional = if f.is_optional() { "?" } else { "" };
+                format!("`{}{}`", f.name(), optional)
+            })
+            .collect();
+        writeln!(
+            out,
+            "| {} | [`{}`](#{}) | {} | {} | {} |",
+            msg.kind_code(),
+            msg.kind_name(),
+            anchor(msg),
+            md_cell(msg.ty()),
+            md_cell(&msg.stages().join(", ")),
+            fields.join(", "),
+        )?;
+    }
+    for msg in spec.message_iter() {
+        writeln!(out)?;
+        writeln!(out, "### {}", msg.kind_name())?;
+        writeln!(out)?;
+        if !msg.desc().is_empty() {
+            writeln!(out, "{}", msg.desc().trim())?;
+            writeln!(out)?;
+        }
+        writeln!(out, "- Code: `{}`", msg.kind_code())?;
+        writeln!(out, "- Type: {}", msg.ty())?;
+        writeln!(out, "- Stages: {}", msg.stages().join(", "))?;
+        if msg.field_iter().len() == 0 {
+            continue;
+        }
+        writeln!(out)?;
+        writeln!(out, "| # | Field | Type | Required | Description |")?;
+        writeln!(out, "| -: | ----- | ---- | -------- | ----------- |")?;
+        for (i, field) in msg.field_iter().enumerate() {
+            writeln!(
+                out,
+                "| {} | `{}` | `{}` | {} | {} |",
+                i + 1,
+                field.name(),
+                field.ty(),
+                if field.is_optional() { "no" } else { "yes" },
+                md_cell(field.desc()),
+            )?;
+        }
+    }
+    writeln!(out)?;
+    writeln!(out, "## URIs")?;
+    writeln!(out)?;
+    writeln!(out, "| URI | Description |")?;
+    writeln!(out, "| --- | ----------- |")?;
+    for uri_def in spec.uri_iter() {
+        writeln!(out, "| `{}` | {} |", uri_def.uri(), md_cell(uri_def.desc()))?;
+    }
+    Ok(())
+}
+
+fn render_html(spec: &Spec, out: &mut String) -> fmt::Result {
+    let title = format!("LRPM Protocol {}", spec.version());
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", html_escape(&title))?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>{}</h1>", html_escape(&title))?;
+    writeln!(out, "<h2>Messages</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Code</th><th>Name</th><th>Type</th><th>Stages</th><th>Fields</th></tr>"
+    )?;
+    for msg in spec.message_iter() {
+        let fields: Vec<_> = msg
+            .field_iter()
+            .map(|f| {
+                let optional = if f.is_optional() { "?" } else { "" };
+                format!("<code>{}{}</code>", html_escape(f.name()), optional)
+            })
+            .collect();
+        writeln!(
+            out,
+            "<tr><td>{}</td><td><a href=\"#{}\"><code>{}</code></a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            msg.kind_code(),
+            anchor(msg),
+            html_escape(msg.kind_name()),
+            html_escape(msg.ty()),
+            html_escape(&msg.stages().join(", ")),
+            fields.join(", "),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    for msg in spec.message_iter() {
+        writeln!(
+            out,
+            "<h3 id=\"{}\">{}</h3>",
+            anchor(msg),
+            html_escape(msg.kind_name())
+        )?;
+        if !msg.desc().is_empty() {
+            writeln!(out, "<p>{}</p>", html_escape(msg.desc().trim()))?;
+        }
+        writeln!(out, "<ul>")?;
+        writeln!(out, "<li>Code: <code>{}</code></li>", msg.kind_code())?;
+        writeln!(out, "<li>Type: {}</li>", html_escape(msg.ty()))?;
+        writeln!(
+            out,
+            "<li>Stages: {}</li>",
+            html_escape(&msg.stages().join(", "))
+        )?;
+        writeln!(out, "</ul>")?;
+        if msg.field_iter().len() == 0 {
+            continue;
+        }
+        writeln!(out, "<table>")?;
+        writeln!(
+            out,
+            "<tr><th>#</th><th>Field</th><th>Type</th><th>Required</th><th>Description</th></tr>"
+        )?;
+        for (i, field) in msg.field_iter().enumerate() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td><code>{}</code></td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                i + 1,
+                html_escape(field.name()),
+                html_escape(field.ty()),
+                if field.is_optional() { "no" } else { "yes" },
+                html_escape(field.desc()),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "<h2>URIs</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(out, "<tr><th>URI</th><th>Description</th></tr>")?;
+    for uri_def in spec.uri_iter() {
+        writeln!(
+            out,
+            "<tr><td><code>{}</code></td><td>{}</td></tr>",
+            html_escape(uri_def.uri()),
+            html_escape(uri_def.desc()),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+/// Returns the fragment a message section is linked by, as GitHub derives
+/// it from the Markdown heading.
+fn anchor(msg: &MsgDef) -> String {
+    msg.kind_name().to_lowercase()
+}
+
+/// Puts text on one line and escapes the table cell delimiter.
+fn md_cell(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_docs() {
+        let spec: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 7
+            name = "FOO_BAR"
+            type = "request"
+            stages = ["established"]
+            desc = "Foos a <bar>."
+            fields = [
+                { name = "id", type = "Id", desc = "The request | ID." },
+                { name = "meta", type = "Meta", desc = "", optional = true },
+            ]
+
+            [[uri_definitions]]
+            uri = "error.foo"
+            desc = "Not foo."
+        "#
+        .parse()
+        .unwrap();
+        let md = render(&spec, DocFormat::Markdown);
+        assert!(
+            md.contains("| 7 | [`FOO_BAR`](#foo_bar) | request | established | `id`, `meta?` |\n")
+        );
+        assert!(md.contains("| 1 | `id` | `Id` | yes | The request \\| ID. |\n"));
+        assert!(md.contains("| `error.foo` | Not foo. |\n"));
+        let html = render(&spec, DocFormat::Html);
+        assert!(html.contains("<h3 id=\"foo_bar\">FOO_BAR</h3>\n<p>Foos a &lt;bar&gt;.</p>\n"));
+    }
+}
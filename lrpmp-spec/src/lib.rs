@@ -1,9 +1,15 @@
+mod diff;
 mod message;
 mod validation;
 
+pub mod docs;
 pub mod naming;
+pub mod stubs;
 pub mod uri;
 
+#[cfg(feature = "codegen")]
+pub mod codegen;
+
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
@@ -14,8 +20,10 @@ use serde::Deserialize;
 
 use self::naming::{default_naming, NamingConvention};
 
+pub use self::diff::{Change, ChangeKind, SpecDiff};
 pub use self::message::*;
 pub use self::uri::UriDef;
+pub use self::validation::{Definition, Problem, ValidationError};
 pub use crate::errors::Error;
 
 pub mod errors {
@@ -28,6 +36,32 @@ pub mod errors {
         }
         errors {
             NoDefaultSpec
+            IncompatibleSpec(changes: Vec<crate::Change>) {
+                description("incompatible spec")
+                display(
+                    "incompatible spec: {}",
+                    changes.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                )
+            }
+            UnknownLanguage(name: String) {
+                description("unknown language")
+                display("unknown language `{}`", name)
+            }
+            UnknownDocFormat(name: String) {
+                description("unknown doc format")
+                display("unknown doc format `{}`", name)
+            }
+            NoOutDir {
+                description("`OUT_DIR` is not set")
+                display("`OUT_DIR` is not set, expected to be run from a build script")
+            }
+            InvalidSpec(errors: Vec<crate::ValidationError>) {
+                description("invalid spec")
+                display(
+                    "invalid spec: {}",
+                    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                )
+            }
         }
     }
 }
@@ -85,6 +119,30 @@ impl Spec {
         self::validation::run(self)
     }
 
+    /// Returns the changes from this spec to `other`.
+    pub fn diff(&self, other: &Spec) -> SpecDiff {
+        self::diff::run(self, other)
+    }
+
+    /// Checks peers using `other` can read what peers using this spec send.
+    ///
+    /// Compatibility is one-way: `other` may add message kinds and optional
+    /// fields, which peers using this spec don't know about. Peers using
+    /// this spec must be upgraded before peers using `other` send any of
+    /// them; checking the other way round reports the additions as removals.
+    ///
+    /// A spec loaded at runtime can be checked against the one this crate
+    /// was compiled against with `Spec::current()?.check_compatible(&spec)`.
+    pub fn check_compatible(&self, other: &Spec) -> Result<SpecDiff, Error> {
+        let diff = self.diff(other);
+        if diff.is_breaking() {
+            let changes = diff.breaking().cloned().collect();
+            Err(self::errors::ErrorKind::IncompatibleSpec(changes).into())
+        } else {
+            Ok(diff)
+        }
+    }
+
     /// Recursively renames names and types given a naming convention.
     pub fn rename(self, naming: &'static NamingConvention) -> Self {
         if self.inner.naming == naming {
@@ -94,6 +152,9 @@ impl Spec {
         for msg in spec_inner.messages.iter_mut() {
             msg.rename(naming);
         }
+        for uri_def in spec_inner.uri_definitions.iter_mut() {
+            uri_def.rename(naming);
+        }
         spec_inner.naming = naming;
         spec_inner.into()
     }
@@ -133,4 +194,201 @@ mod tests {
             .validate()
             .unwrap();
     }
+
+    #[test]
+    fn test_invalid_spec() {
+        let spec: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 1
+            name = "FOO_BAR"
+            type = "request"
+            stages = ["established", "opening"]
+            desc = ""
+            fields = [
+                { name = "id", type = "Id", desc = "" },
+                { name = "value", type = "U32", desc = "" },
+                { name = "meta", type = "Meta", desc = "", optional = true },
+                { name = "uri", type = "Uri", desc = "" },
+            ]
+
+            [[messages]]
+            code = 1
+            name = "FOO__BAR"
+            type = "request"
+            stages = ["established"]
+            desc = ""
+            fields = []
+
+            [[uri_definitions]]
+            uri = "error.Bad"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        let errors = match spec.rename(RUST_NAMING_CONVENTION).validate() {
+            Err(Error(errors::ErrorKind::InvalidSpec(errors), _)) => errors,
+            other => panic!("unexpected result {:?}", other),
+        };
+        let foo_bar = Definition::Message {
+            code: 1,
+            name: "FOO_BAR".into(),
+        };
+        let foo_bar_2 = Definition::Message {
+            code: 1,
+            name: "FOO__BAR".into(),
+        };
+        let problems: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.definition, err.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (foo_bar.clone(), Problem::UnknownStage("opening".into())),
+                (
+                    Definition::Field {
+                        message: "FOO_BAR".into(),
+                        name: "value".into(),
+                    },
+                    Problem::UnknownFieldType("U32".into()),
+                ),
+                (
+                    Definition::Field {
+                        message: "FOO_BAR".into(),
+                        name: "uri".into(),
+                    },
+                    Problem::RequiredAfterOptional,
+                ),
+                (foo_bar_2.clone(), Problem::DuplicateCode),
+                (
+                    foo_bar_2,
+                    Problem::IdentifierCollision {
+                        ident: "FooBar".into(),
+                        with: foo_bar,
+                    },
+                ),
+                (
+                    Definition::Uri {
+                        uri: "error.Bad".into(),
+                    },
+                    Problem::InvalidUri {
+                        invalid: 'B',
+                        offset: 6,
+                        reason: "invalid char",
+                    },
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spec_diff() {
+        let from: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 1
+            name = "FOO"
+            type = "request"
+            stages = ["established"]
+            desc = ""
+            fields = [
+                { name = "id", type = "Id", desc = "" },
+                { name = "uri", type = "Uri", desc = "" },
+            ]
+
+            [[uri_definitions]]
+            uri = "error.foo"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        let to: Spec = r#"
+            version = "0.2.0"
+
+            [[messages]]
+            code = 1
+            name = "FOO"
+            type = "request"
+            stages = ["established", "closing"]
+            desc = ""
+            fields = [
+                { name = "id", type = "Id", desc = "" },
+                { name = "foo_uri", type = "Uri", desc = "" },
+                { name = "meta", type = "Meta", desc = "", optional = true },
+            ]
+
+            [[messages]]
+            code = 2
+            name = "BAR"
+            type = "request"
+            stages = ["established"]
+            desc = ""
+            fields = []
+
+            [[uri_definitions]]
+            uri = "error.foo"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        let diff = from.diff(&to);
+        assert!(!diff.is_breaking());
+        assert_eq!(
+            diff.changes
+                .iter()
+                .map(|c| (c.kind.clone(), c.breaking))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    ChangeKind::StagesChanged {
+                        added: vec!["closing".into()],
+                        removed: vec![],
+                    },
+                    false
+                ),
+                (ChangeKind::Renamed { from: "uri".into() }, false),
+                (ChangeKind::Added, false),
+                (ChangeKind::Added, false),
+            ]
+        );
+        let changes = match to.check_compatible(&from) {
+            Err(Error(errors::ErrorKind::IncompatibleSpec(changes), _)) => changes,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            changes
+                .into_iter()
+                .map(|c| (c.definition, c.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Definition::Message {
+                        code: 1,
+                        name: "FOO".into()
+                    },
+                    ChangeKind::StagesChanged {
+                        added: vec![],
+                        removed: vec!["closing".into()],
+                    },
+                ),
+                (
+                    Definition::Field {
+                        message: "FOO".into(),
+                        name: "meta".into()
+                    },
+                    ChangeKind::Removed,
+                ),
+                (
+                    Definition::Message {
+                        code: 2,
+                        name: "BAR".into()
+                    },
+                    ChangeKind::Removed,
+                ),
+            ]
+        );
+    }
 }
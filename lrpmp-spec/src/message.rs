@@ -1,5 +1,11 @@
 use crate::{default_naming, Deserialize, NamingConvention};
 
+/// The field types understood by the codegen.
+pub const FIELD_TYPES: &[&str] = &["Id", "Uri", "Kind", "Meta", "Body"];
+
+/// The stages of a session a message may be allowed in.
+pub const STAGES: &[&str] = &["handshake", "established", "closing", "closed"];
+
 #[derive(Debug, Clone, Deserialize)]
 struct MsgDefInner {
     code: u8,
@@ -80,6 +86,8 @@ pub struct MsgFieldDef {
     #[serde(rename = "type")]
     ty: String,
     desc: String,
+    #[serde(default)]
+    optional: bool,
     #[serde(default = "default_naming", skip)]
     naming: &'static NamingConvention,
 }
@@ -97,6 +105,11 @@ impl MsgFieldDef {
         self.desc.as_ref()
     }
 
+    /// Returns whether the field may be left off the end of a message.
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
     pub fn rename(&mut self, naming: &'static NamingConvention) {
         if self.naming == naming {
             return;
@@ -2,8 +2,8 @@ use std::cmp::PartialEq;
 use std::fmt;
 
 pub use inflector::cases::{
-    pascalcase::to_pascal_case, screamingsnakecase::to_screaming_snake_case,
-    snakecase::to_snake_case,
+    camelcase::to_camel_case, pascalcase::to_pascal_case,
+    screamingsnakecase::to_screaming_snake_case, snakecase::to_snake_case,
 };
 
 pub struct NamingConvention {
@@ -52,3 +52,13 @@ pub const RUST_NAMING_CONVENTION: &NamingConvention = &NamingConvention {
     msg_field_name: to_snake_case,
     msg_field_type: to_pascal_case,
 };
+
+/// PascalCase message names with camelCase field names, as in TypeScript.
+pub const CAMEL_CASE_NAMING_CONVENTION: &NamingConvention = &NamingConvention {
+    name: "camel_case",
+    uri_name: to_screaming_snake_case,
+    msg_name: to_pascal_case,
+    msg_type: to_pascal_case,
+    msg_field_name: to_camel_case,
+    msg_field_type: to_pascal_case,
+};
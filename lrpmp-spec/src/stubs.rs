@@ -0,0 +1,298 @@
+//! Client stub generation for other languages.
+//!
+//! The stubs hold the message kind codes, a class per message that converts
+//! to and from the message array, and the URI constants of a spec.
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::errors::ErrorKind;
+use crate::naming::{NamingConvention, CAMEL_CASE_NAMING_CONVENTION, RUST_NAMING_CONVENTION};
+use crate::{Error, MsgDef, Spec};
+
+/// A language stubs can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    TypeScript,
+    Python,
+}
+
+impl Language {
+    /// Returns the naming convention of the language.
+    pub fn naming(self) -> &'static NamingConvention {
+        match self {
+            Self::TypeScript => CAMEL_CASE_NAMING_CONVENTION,
+            // Python names things as Rust does.
+            Self::Python => RUST_NAMING_CONVENTION,
+        }
+    }
+
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Self::TypeScript => "ts",
+            Self::Python => "py",
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "typescript" | "ts" => Ok(Self::TypeScript),
+            "python" | "py" => Ok(Self::Python),
+            _ => Err(ErrorKind::UnknownLanguage(s.to_string()).into()),
+        }
+    }
+}
+
+/// Generates the stubs of a spec, renamed to the language's convention.
+pub fn generate(spec: &Spec, language: Language) -> String {
+    let spec = spec.clone().rename(language.naming());
+    let mut out = String::new();
+    let result = match language {
+        Language::TypeScript => gen_typescript(&spec, &mut out),
+        Language::Python => gen_python(&spec, &mut out),
+    };
+    // Writing to a `String` can't fail.
+    result.unwrap();
+    out
+}
+
+fn gen_typescript(spec: &Spec, out: &mut String) -> fmt::Result {
+    writeln!(out, "{}", header("//", spec))?;
+    out.push_str(
+        "export type Id = number;\n\
+         export type Uri = string;\n\
+         export type Kind = number | string;\n\
+         export type Meta = { [key: string]: unknown };\n\
+         export type Body = unknown;\n\n",
+    );
+
+    out.push_str("export enum MessageKind {\n");
+    for msg in spec.message_iter() {
+        writeln!(out, "  {} = {},", msg.name(), msg.kind_code())?;
+    }
+    out.push_str("}\n");
+
+    for msg in spec.message_iter() {
+        let class = format!("{}Message", msg.name());
+        let min_len = min_array_len(msg);
+        writeln!(out)?;
+        writeln!(out, "/** {} */", doc_line(msg.desc()))?;
+        writeln!(out, "export class {} {{", class)?;
+        writeln!(out, "  static readonly kind = MessageKind.{};", msg.name())?;
+        writeln!(out)?;
+        writeln!(out, "  constructor(")?;
+        for field in msg.field_iter() {
+            let optional = if field.is_optional() { "?" } else { "" };
+            if !field.desc().is_empty() {
+                writeln!(out, "    /** {} */", doc_line(field.desc()))?;
+            }
+            writeln!(
+                out,
+                "    public {}{}: {},",
+                field.name(),
+                optional,
+                field.ty()
+            )?;
+        }
+        writeln!(out, "  ) {{}}")?;
+        writeln!(out)?;
+        writeln!(out, "  toArray(): unknown[] {{")?;
+        write!(out, "    const array: unknown[] = [{}.kind", class)?;
+        for field in msg.field_iter() {
+            write!(out, ", this.{}", field.name())?;
+        }
+        writeln!(out, "];")?;
+        writeln!(
+            out,
+            "    while (array.length > {} && array[array.length - 1] === undefined) {{",
+            min_len
+        )?;
+        writeln!(out, "      array.pop();")?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "    return array;")?;
+        writeln!(out, "  }}")?;
+        writeln!(out)?;
+        writeln!(out, "  static fromArray(array: unknown[]): {} {{", class)?;
+        write!(out, "    return new {}(", class)?;
+        for (i, field) in msg.field_iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            write!(out, "array[{}] as {}", i + 1, field.ty())?;
+        }
+        writeln!(out, ");")?;
+        writeln!(out, "  }}")?;
+        writeln!(out, "}}")?;
+    }
+
+    writeln!(out)?;
+    for uri_def in spec.uri_iter() {
+        if !uri_def.desc().is_empty() {
+            writeln!(out, "/** {} */", doc_line(uri_def.desc()))?;
+        }
+        writeln!(
+            out,
+            "export const {}_URI: Uri = {:?};",
+            uri_def.name(),
+            uri_def.uri()
+        )?;
+    }
+    Ok(())
+}
+
+fn gen_python(spec: &Spec, out: &mut String) -> fmt::Result {
+    writeln!(out, "{}", header("#", spec))?;
+    out.push_str(
+        "from enum import IntEnum\n\
+         from typing import Any, Dict, List, Optional, Union\n\n\
+         Id = int\n\
+         Uri = str\n\
+         Kind = Union[int, str]\n\
+         Meta = Dict[str, Any]\n\
+         Body = Any\n\n\n",
+    );
+
+    writeln!(out, "class MessageKind(IntEnum):")?;
+    for msg in spec.message_iter() {
+        writeln!(out, "    {} = {}", msg.kind_name(), msg.kind_code())?;
+    }
+
+    for msg in spec.message_iter() {
+        let class = format!("{}Message", msg.name());
+        let min_len = min_array_len(msg);
+        writeln!(out, "\n")?;
+        writeln!(out, "class {}:", class)?;
+        writeln!(out, "    \"\"\"{}\"\"\"", py_docstring(msg.desc()))?;
+        writeln!(out)?;
+        writeln!(out, "    kind = MessageKind.{}", msg.kind_name())?;
+        writeln!(out)?;
+        write!(out, "    def __init__(self")?;
+        for field in msg.field_iter() {
+            if field.is_optional() {
+                write!(out, ", {}: Optional[{}] = None", field.name(), field.ty())?;
+            } else {
+                write!(out, ", {}: {}", field.name(), field.ty())?;
+            }
+        }
+        writeln!(out, ") -> None:")?;
+        if msg.field_iter().len() == 0 {
+            writeln!(out, "        pass")?;
+        }
+        for field in msg.field_iter() {
+            if !field.desc().is_empty() {
+                writeln!(out, "        # {}", doc_line(field.desc()))?;
+            }
+            writeln!(out, "        self.{0} = {0}", field.name())?;
+        }
+        writeln!(out)?;
+        writeln!(out, "    def to_list(self) -> List[Any]:")?;
+        write!(out, "        fields = [int(self.kind)")?;
+        for field in msg.field_iter() {
+            write!(out, ", self.{}", field.name())?;
+        }
+        writeln!(out, "]")?;
+        writeln!(
+            out,
+            "        while len(fields) > {} and fields[-1] is None:",
+            min_len
+        )?;
+        writeln!(out, "            fields.pop()")?;
+        writeln!(out, "        return fields")?;
+        writeln!(out)?;
+        writeln!(out, "    @classmethod")?;
+        writeln!(
+            out,
+            "    def from_list(cls, fields: List[Any]) -> \"{}\":",
+            class
+        )?;
+        writeln!(out, "        return cls(*fields[1:])")?;
+    }
+
+    writeln!(out, "\n")?;
+    for uri_def in spec.uri_iter() {
+        if !uri_def.desc().is_empty() {
+            writeln!(out, "# {}", doc_line(uri_def.desc()))?;
+        }
+        writeln!(out, "{}_URI: Uri = {:?}", uri_def.name(), uri_def.uri())?;
+    }
+    Ok(())
+}
+
+fn header(comment: &str, spec: &Spec) -> String {
+    format!(
+        "{} Generated by lrpmp-spec from spec version {}. Do not edit.\n",
+        comment,
+        spec.version()
+    )
+}
+
+/// Returns the length of the message array without optional fields, kind included.
+fn min_array_len(msg: &MsgDef) -> usize {
+    msg.field_iter().filter(|f| !f.is_optional()).count() + 1
+}
+
+/// Collapses a description onto one line that can't end a comment.
+fn doc_line(desc: &str) -> String {
+    desc.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("*/", "* /")
+}
+
+/// Collapses a description onto one line to put in a Python docstring,
+/// escaping the backslashes and quotes that would change or end it.
+fn py_docstring(desc: &str) -> String {
+    doc_line(desc).replace('\\', r"\\").replace('"', r#"\""#)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_stubs() {
+        let spec: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 7
+            name = "FOO_BAR"
+            type = "request"
+            stages = ["established"]
+            desc = "Foos a bar."
+            fields = [
+                { name = "request_id", type = "Id", desc = "" },
+                { name = "meta", type = "Meta", desc = "", optional = true },
+            ]
+
+            [[uri_definitions]]
+            uri = "error.foo"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        let ts = generate(&spec, Language::TypeScript);
+        assert!(ts.contains("export enum MessageKind {\n  FooBar = 7,\n}"));
+        assert!(ts.contains("    public requestId: Id,\n"));
+        assert!(ts.contains("    public meta?: Meta,\n"));
+        assert!(ts.contains("export const ERROR_FOO_URI: Uri = \"error.foo\";"));
+        let py = generate(&spec, Language::Python);
+        assert!(py.contains("class MessageKind(IntEnum):\n    FOO_BAR = 7\n"));
+        assert!(py.contains(
+            "    def __init__(self, request_id: Id, meta: Optional[Meta] = None) -> None:\n"
+        ));
+        assert!(py.contains("        while len(fields) > 2 and fields[-1] is None:\n"));
+    }
+
+    #[test]
+    fn test_python_docstring_escapes() {
+        assert_eq!(
+            py_docstring(r#"Reads C:\New\x as a "path""#),
+            r#"Reads C:\\New\\x as a \"path\""#
+        );
+        assert_eq!(py_docstring(r#"Says """hi""""#), r#"Says \"\"\"hi\"\"\""#);
+    }
+}
@@ -1,6 +1,208 @@
-use super::{Error, Spec};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::errors::ErrorKind;
+use super::naming::{NamingConvention, DEFAULT_NAMING_CONVENTION};
+use super::{uri, Error, Spec, FIELD_TYPES, STAGES};
+
+/// Points to a definition within a spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Message { code: u8, name: String },
+    Field { message: String, name: String },
+    Uri { uri: String },
+}
+
+impl fmt::Display for Definition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Message { code, name } => write!(f, "message `{}` (code {})", name, code),
+            Self::Field { message, name } => write!(f, "field `{}` of message `{}`", name, message),
+            Self::Uri { uri } => write!(f, "uri `{}`", uri),
+        }
+    }
+}
+
+/// A problem with a definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The message code is used by an earlier message.
+    DuplicateCode,
+    /// The message name is used by an earlier message.
+    DuplicateName,
+    /// The uri is defined earlier.
+    DuplicateUri,
+    /// The field type is not one of [`FIELD_TYPES`].
+    UnknownFieldType(String),
+    /// The field is required but follows an optional field.
+    RequiredAfterOptional,
+    /// The stage is not one of [`STAGES`].
+    UnknownStage(String),
+    /// The uri failed [`uri::validate_bytes`].
+    InvalidUri {
+        invalid: char,
+        offset: usize,
+        reason: &'static str,
+    },
+    /// The identifier after renaming is used by an earlier definition.
+    IdentifierCollision { ident: String, with: Definition },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DuplicateCode => f.write_str("duplicate code"),
+            Self::DuplicateName => f.write_str("duplicate name"),
+            Self::DuplicateUri => f.write_str("duplicate uri"),
+            Self::UnknownFieldType(ty) => write!(f, "unknown field type `{}`", ty),
+            Self::RequiredAfterOptional => f.write_str("required field after an optional field"),
+            Self::UnknownStage(stage) => write!(f, "unknown stage `{}`", stage),
+            Self::InvalidUri {
+                invalid,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "invalid char `{}` at offset {} ({})",
+                invalid, offset, reason
+            ),
+            Self::IdentifierCollision { ident, with } => {
+                write!(f, "identifier `{}` collides with {}", ident, with)
+            }
+        }
+    }
+}
+
+/// A problem found validating a spec, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub definition: Definition,
+    pub problem: Problem,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.definition, self.problem)
+    }
+}
 
 pub fn run(spec: Spec) -> Result<Spec, Error> {
-    // TODO
-    Ok(spec)
+    let mut validator = Validator::default();
+    validator.check_messages(&spec);
+    validator.check_uris(&spec);
+    if validator.errors.is_empty() {
+        Ok(spec)
+    } else {
+        Err(ErrorKind::InvalidSpec(validator.errors).into())
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    fn report(&mut self, definition: &Definition, problem: Problem) {
+        self.errors.push(ValidationError {
+            definition: definition.clone(),
+            problem,
+        });
+    }
+
+    fn check_ident(
+        &mut self,
+        seen: &mut HashMap<String, Definition>,
+        ident: &str,
+        definition: &Definition,
+    ) {
+        if let Some(with) = seen.get(ident) {
+            let problem = Problem::IdentifierCollision {
+                ident: ident.to_string(),
+                with: with.clone(),
+            };
+            self.report(definition, problem);
+        } else {
+            seen.insert(ident.to_string(), definition.clone());
+        }
+    }
+
+    fn check_messages(&mut self, spec: &Spec) {
+        let field_types: Vec<_> = FIELD_TYPES
+            .iter()
+            .map(|ty| renamed(spec.inner.naming, spec.inner.naming.msg_field_type, ty))
+            .collect();
+        let mut codes = HashSet::new();
+        let mut names = HashSet::new();
+        let mut idents = HashMap::new();
+        for msg in spec.message_iter() {
+            let definition = Definition::Message {
+                code: msg.kind_code(),
+                name: msg.kind_name().to_string(),
+            };
+            if !codes.insert(msg.kind_code()) {
+                self.report(&definition, Problem::DuplicateCode);
+            }
+            if !names.insert(msg.kind_name()) {
+                self.report(&definition, Problem::DuplicateName);
+            } else {
+                self.check_ident(&mut idents, msg.name(), &definition);
+            }
+            for stage in msg.stages() {
+                if !STAGES.contains(&stage.as_str()) {
+                    self.report(&definition, Problem::UnknownStage(stage.clone()));
+                }
+            }
+            let mut field_idents = HashMap::new();
+            let mut optional = false;
+            for field in msg.field_iter() {
+                let definition = Definition::Field {
+                    message: msg.kind_name().to_string(),
+                    name: field.name().to_string(),
+                };
+                if !field_types.iter().any(|ty| ty == field.ty()) {
+                    let problem = Problem::UnknownFieldType(field.ty().to_string());
+                    self.report(&definition, problem);
+                }
+                if field.is_optional() {
+                    optional = true;
+                } else if optional {
+                    self.report(&definition, Problem::RequiredAfterOptional);
+                }
+                self.check_ident(&mut field_idents, field.name(), &definition);
+            }
+        }
+    }
+
+    fn check_uris(&mut self, spec: &Spec) {
+        let mut uris = HashSet::new();
+        let mut idents = HashMap::new();
+        for uri_def in spec.uri_iter() {
+            let definition = Definition::Uri {
+                uri: uri_def.uri().to_string(),
+            };
+            if let Err(err) = uri::validate_bytes(uri_def.uri().as_bytes()) {
+                let problem = Problem::InvalidUri {
+                    invalid: err.invalid,
+                    offset: err.offset,
+                    reason: err.reason,
+                };
+                self.report(&definition, problem);
+            }
+            if !uris.insert(uri_def.uri()) {
+                self.report(&definition, Problem::DuplicateUri);
+            } else {
+                self.check_ident(&mut idents, uri_def.name(), &definition);
+            }
+        }
+    }
+}
+
+/// Applies a naming convention rule, which the default convention has none of.
+fn renamed(naming: &NamingConvention, rule: fn(&str) -> String, s: &str) -> String {
+    if naming == DEFAULT_NAMING_CONVENTION {
+        s.to_string()
+    } else {
+        rule(s)
+    }
 }
@@ -1,6 +1,25 @@
+use std::collections::HashMap;
 use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
-use super::{Error, Meta, Uri};
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::channel::oneshot;
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::{self, FuturesUnordered};
+use futures::{pin_mut, select, StreamExt};
+
+use super::message::BusMessage;
+use super::session::{Session, SessionDetails, Stage};
+use super::subscription::{Event, Subscription};
+use super::transport::Transport;
+use super::version::{Capabilities, MetaValue, NegotiatedVersion};
+use super::{match_policy_meta, Error, Meta, RemoteError, Uri};
+use crate::codec::generic::Map;
+use crate::message::*;
+use crate::types::{Body, Id, MatchPolicy, UriTrie};
+use crate::uris;
 
 pub trait RpcClient<V> {
     type Future: Future<Output = Result<(V, Meta<V>), Error<V>>>;
@@ -12,3 +31,951 @@ pub trait RpcClient<V> {
 
     fn call_with_meta(&mut self, procedure: &Uri, body: V, meta: Meta<V>) -> Self::Future;
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+type Reply<V> = Result<StandardMessage<Map<V>, V>, Error<V>>;
+
+type HandlerResult<V> = Result<(V, Meta<V>), RemoteError<V>>;
+
+type BoxHandler<V> =
+    Arc<dyn Fn(Uri, Body<V>, Meta<V>) -> BoxFuture<'static, HandlerResult<V>> + Send + Sync>;
+
+struct Procedure<V> {
+    id: u64,
+    handler: BoxHandler<V>,
+}
+
+struct Subscriber<V> {
+    id: u64,
+    tx: UnboundedSender<Event<V>>,
+}
+
+struct ClientState<V> {
+    next_request_id: u64,
+    next_procedure_id: u64,
+    next_subscriber_id: u64,
+    session: Session<V>,
+    established: Option<oneshot::Sender<()>>,
+    session_waiters: Vec<oneshot::Sender<SessionDetails<V>>>,
+    pending: HashMap<u64, oneshot::Sender<Reply<V>>>,
+    procedures: UriTrie<Procedure<V>>,
+    subscribers: UriTrie<Subscriber<V>>,
+}
+
+impl<V> ClientState<V> {
+    fn next_request_id(&mut self) -> Id {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        Id::new(id)
+    }
+
+    /// Returns `true` if requests can still be made in the session.
+    fn is_open(&self) -> bool {
+        match self.session.stage() {
+            Stage::Handshake | Stage::Established => true,
+            Stage::Closing | Stage::Closed => false,
+        }
+    }
+
+    /// Releases requests and waiters held back by the handshake.
+    fn establish(&mut self)
+    where
+        V: Clone,
+    {
+        let details = match self.session.peer() {
+            Some(details) => details.clone(),
+            None => return,
+        };
+        if let Some(established) = self.established.take() {
+            let _ = established.send(());
+        }
+        for waiter in self.session_waiters.drain(..) {
+            let _ = waiter.send(details.clone());
+        }
+    }
+
+    fn respond(&mut self, id: Id, reply: Reply<V>) {
+        if let Some(tx) = self.pending.remove(&u64::from(id)) {
+            // The caller is no longer interested if this fails.
+            let _ = tx.send(reply);
+        }
+    }
+
+    /// Returns the handler for the most specific procedure matching.
+    fn handler(&self, procedure: &Uri) -> Option<BoxHandler<V>> {
+        self.procedures
+            .matches(procedure)
+            .next()
+            .map(|(_, procedure)| procedure.handler.clone())
+    }
+
+    fn add_procedure(
+        &mut self,
+        procedure: Uri,
+        policy: MatchPolicy,
+        handler: BoxHandler<V>,
+    ) -> u64 {
+        let id = self.next_procedure_id;
+        self.next_procedure_id += 1;
+        self.procedures
+            .insert(procedure, policy, Procedure { id, handler });
+        id
+    }
+
+    /// Removes a handler, leaving any other registered for the procedure
+    /// under the policy.
+    fn remove_procedure(&mut self, procedure: &Uri, policy: MatchPolicy, id: u64) {
+        self.procedures
+            .remove_if(procedure, policy, |procedure| procedure.id == id);
+    }
+
+    fn add_subscriber(
+        &mut self,
+        topic: Uri,
+        policy: MatchPolicy,
+        tx: UnboundedSender<Event<V>>,
+    ) -> u64 {
+        let id = self.next_subscriber_id;
+        self.next_subscriber_id += 1;
+        self.subscribers
+            .insert(topic, policy, Subscriber { id, tx });
+        id
+    }
+
+    /// Removes a subscriber, returning `true` if it was the last one
+    /// subscribed to its topic under the policy.
+    fn remove_subscriber(&mut self, topic: &Uri, policy: MatchPolicy, id: u64) -> bool {
+        self.subscribers
+            .remove_if(topic, policy, |s| s.id == id)
+            .is_some()
+            && self.subscribers.get(topic, policy).is_empty()
+    }
+
+    /// Delivers an event to every subscriber with a matching topic.
+    fn deliver(&mut self, event: EventMessage<Map<V>, V>)
+    where
+        V: Clone,
+    {
+        let topic = event.uri;
+        let body = event.body.into_inner();
+        let meta = event.meta;
+        let mut dropped = Vec::new();
+        for (_, s) in self.subscribers.matches(&topic) {
+            let event = Event::new(topic.clone(), body.clone(), meta.clone());
+            if s.tx.unbounded_send(event).is_err() {
+                dropped.push(s.id);
+            }
+        }
+        // Drop subscribers whose subscription was dropped.
+        if !dropped.is_empty() {
+            self.subscribers.retain(|s| !dropped.contains(&s.id));
+        }
+    }
+}
+
+/// A client driving a single [`Transport`].
+///
+/// Constructing a client also returns the connection future which reads
+/// and writes messages on the transport, runs registered procedure
+/// handlers and delivers events to subscriptions. It must be polled
+/// (usually by spawning it) for requests to make progress.
+///
+/// The connection first says hello with the client's session details.
+/// Requests made before the remote peer says hello back are held until
+/// the session is established. The connection completes when the
+/// transport is closed, the session is closed, or every client handle and
+/// subscription is dropped. A remote peer sending a message not allowed
+/// in the current stage of the session closes the connection.
+pub struct Client<V> {
+    tx: UnboundedSender<BusMessage<V>>,
+    state: Arc<Mutex<ClientState<V>>>,
+}
+
+impl<V> Client<V> {
+    /// Constructs a new client and its connection given a transport and
+    /// the details to say hello with.
+    pub fn new<T>(transport: T, details: SessionDetails<V>) -> (Self, impl Future<Output = ()>)
+    where
+        T: Transport<V>,
+        V: MetaValue + Send + 'static,
+    {
+        Self::with_optional_capabilities(transport, details, None)
+    }
+
+    /// Constructs a new client that says hello with its capabilities and
+    /// negotiates a version with the remote peer.
+    ///
+    /// The connection completes without the session being established if
+    /// the remote peer shares no version with the client.
+    pub fn with_capabilities<T>(
+        transport: T,
+        details: SessionDetails<V>,
+        capabilities: Capabilities,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        T: Transport<V>,
+        V: MetaValue + Send + 'static,
+    {
+        Self::with_optional_capabilities(transport, details, Some(capabilities))
+    }
+
+    fn with_optional_capabilities<T>(
+        transport: T,
+        details: SessionDetails<V>,
+        capabilities: Option<Capabilities>,
+    ) -> (Self, impl Future<Output = ()>)
+    where
+        T: Transport<V>,
+        V: MetaValue + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded();
+        let (established_tx, established_rx) = oneshot::channel();
+        let (body, mut meta) = details.into_parts();
+        let mut session = match capabilities {
+            Some(capabilities) => {
+                capabilities.write_meta(&mut meta);
+                Session::with_capabilities(capabilities)
+            }
+            None => Session::new(),
+        };
+        let hello = HelloMessage::new(Body::new(body), meta);
+        session.send(hello.kind()).unwrap();
+        let state = Arc::new(Mutex::new(ClientState {
+            next_request_id: 1,
+            next_procedure_id: 1,
+            next_subscriber_id: 1,
+            session,
+            established: Some(established_tx),
+            session_waiters: Vec::new(),
+            pending: HashMap::new(),
+            procedures: UriTrie::new(),
+            subscribers: UriTrie::new(),
+        }));
+        let client = Self {
+            tx,
+            state: state.clone(),
+        };
+        let connection = async move {
+            let (sink, stream) = transport.split();
+            let (replies_tx, replies_rx) = mpsc::unbounded();
+
+            let reader = async {
+                let mut stream = stream.fuse();
+                let mut invocations = FuturesUnordered::new();
+                loop {
+                    select! {
+                        message = stream.next() => match message.map(|m| handle(&state, m)) {
+                            Some(Action::Reply(invocation)) => invocations.push(invocation),
+                            Some(Action::None) => (),
+                            Some(Action::Close) | None => break,
+                        },
+                        reply = invocations.select_next_some() => {
+                            let _ = replies_tx.unbounded_send(reply);
+                        }
+                    }
+                }
+            };
+            // Stop writing once every client handle is dropped.
+            let outgoing = rx.map(Some).chain(stream::once(future::ready(None)));
+            let outgoing = stream::select(outgoing, replies_rx.map(Some))
+                .take_while(|m| future::ready(m.is_some()))
+                .filter_map(future::ready);
+            // Say hello, then hold everything else until the session is
+            // established.
+            let writer = stream::once(future::ready(BusMessage::new(hello)))
+                .chain(established_rx.map(move |_| outgoing).flatten_stream())
+                .map(Ok)
+                .forward(sink);
+
+            pin_mut!(reader, writer);
+
+            future::select(reader, writer).await;
+            // Fail every outstanding request and end every subscription.
+            let mut state = state.lock().unwrap();
+            state.session.abort();
+            state.session_waiters.clear();
+            state.pending.clear();
+            state.subscribers.clear();
+        };
+        (client, connection)
+    }
+
+    /// Returns the details the remote peer said hello with, once the
+    /// session is established.
+    pub fn session(&self) -> impl Future<Output = Result<SessionDetails<V>, Error<V>>>
+    where
+        V: Clone,
+    {
+        let mut state = self.state.lock().unwrap();
+        let (tx, rx) = oneshot::channel();
+        match state.session.peer() {
+            Some(details) if state.session.is_established() => {
+                let _ = tx.send(details.clone());
+            }
+            _ if state.session.stage() == Stage::Handshake => state.session_waiters.push(tx),
+            _ => (),
+        }
+        rx.map(|details| details.map_err(|_| Error::Closed))
+    }
+
+    /// Returns the version and features agreed on with the remote peer,
+    /// once negotiated.
+    pub fn negotiated(&self) -> Option<NegotiatedVersion> {
+        let state = self.state.lock().unwrap();
+        state.session.negotiated().cloned()
+    }
+
+    /// Says goodbye to the remote peer, closing the session.
+    ///
+    /// The connection completes once the remote peer says goodbye back.
+    pub fn close(&self) -> Result<(), Error<V>> {
+        let goodbye = GoodbyeMessage::new(uris::CLOSE_NORMAL_URI.clone(), Meta::default());
+        let mut state = self.state.lock().unwrap();
+        state
+            .session
+            .send(goodbye.kind())
+            .map_err(|_| Error::Closed)?;
+        self.tx
+            .unbounded_send(BusMessage::new(goodbye))
+            .map_err(|_| Error::Closed)
+    }
+
+    /// Registers a procedure handler.
+    ///
+    /// The procedure may contain wildcards, in which case the handler is
+    /// invoked for every matching procedure without a more specific
+    /// handler registered. The handler is given the called procedure along
+    /// with the call body and meta, and its result or error is sent back to
+    /// the caller.
+    #[inline]
+    pub fn register<H, F>(
+        &self,
+        procedure: Uri,
+        handler: H,
+    ) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        H: Fn(Uri, Body<V>, Meta<V>) -> F + Send + Sync + 'static,
+        F: Future<Output = HandlerResult<V>> + Send + 'static,
+        V: From<String>,
+    {
+        self.register_with_policy(procedure, MatchPolicy::default(), handler)
+    }
+
+    /// Registers a procedure handler matched under a policy.
+    ///
+    /// The hub routes a call to the registration of highest precedence, as
+    /// does the client to its handlers (see [`UriTrie::matches`]).
+    pub fn register_with_policy<H, F>(
+        &self,
+        procedure: Uri,
+        policy: MatchPolicy,
+        handler: H,
+    ) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        H: Fn(Uri, Body<V>, Meta<V>) -> F + Send + Sync + 'static,
+        F: Future<Output = HandlerResult<V>> + Send + 'static,
+        V: From<String>,
+    {
+        let handler: BoxHandler<V> =
+            Arc::new(move |uri, body, meta| handler(uri, body, meta).boxed());
+        // The handler is added before the hub replies, so it is in place for
+        // a call routed right after, and only it is removed if refused.
+        let handler_id =
+            self.state
+                .lock()
+                .unwrap()
+                .add_procedure(procedure.clone(), policy, handler);
+        let reply = self.request(|id| {
+            let meta = match_policy_meta(policy);
+            BusMessage::new(RegisterMessage::new(id, procedure.clone(), meta))
+        });
+        let state = self.state.clone();
+        async move {
+            match reply.await {
+                Ok(Ok(StandardMessage::Registered(_))) => Ok(()),
+                other => {
+                    state
+                        .lock()
+                        .unwrap()
+                        .remove_procedure(&procedure, policy, handler_id);
+                    Err(unexpected_reply(other))
+                }
+            }
+        }
+    }
+
+    /// Unregisters a procedure handler.
+    #[inline]
+    pub fn unregister(&self, procedure: &Uri) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        V: From<String>,
+    {
+        self.unregister_with_policy(procedure, MatchPolicy::default())
+    }
+
+    /// Unregisters a procedure handler registered under a policy.
+    pub fn unregister_with_policy(
+        &self,
+        procedure: &Uri,
+        policy: MatchPolicy,
+    ) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        V: From<String>,
+    {
+        // Only the handlers in place now are removed, and only once the hub
+        // has stopped routing calls to them.
+        let handler_ids: Vec<_> = self
+            .state
+            .lock()
+            .unwrap()
+            .procedures
+            .get(procedure, policy)
+            .iter()
+            .map(|procedure| procedure.id)
+            .collect();
+        let reply = self.request(|id| {
+            let meta = match_policy_meta(policy);
+            BusMessage::new(UnregisterMessage::new(id, procedure.clone(), meta))
+        });
+        let procedure = procedure.clone();
+        let state = self.state.clone();
+        async move {
+            match reply.await {
+                Ok(Ok(StandardMessage::Unregistered(_))) => {
+                    let mut state = state.lock().unwrap();
+                    for handler_id in handler_ids {
+                        state.remove_procedure(&procedure, policy, handler_id);
+                    }
+                    Ok(())
+                }
+                other => Err(unexpected_reply(other)),
+            }
+        }
+    }
+
+    /// Subscribes to a topic.
+    ///
+    /// The topic may contain wildcards. Events published to a matching
+    /// topic are yielded by the returned subscription until it is
+    /// unsubscribed or the connection closes.
+    #[inline]
+    pub fn subscribe(&self, topic: Uri) -> impl Future<Output = Result<Subscription<V>, Error<V>>>
+    where
+        V: From<String>,
+    {
+        self.subscribe_with_policy(topic, MatchPolicy::default())
+    }
+
+    /// Subscribes to a topic matched under a policy.
+    pub fn subscribe_with_policy(
+        &self,
+        topic: Uri,
+        policy: MatchPolicy,
+    ) -> impl Future<Output = Result<Subscription<V>, Error<V>>>
+    where
+        V: From<String>,
+    {
+        let (tx, rx) = mpsc::unbounded();
+        let id = self
+            .state
+            .lock()
+            .unwrap()
+            .add_subscriber(topic.clone(), policy, tx);
+        let reply = self.request(|id| {
+            let meta = match_policy_meta(policy);
+            BusMessage::new(SubscribeMessage::new(id, topic.clone(), meta))
+        });
+        let client = self.clone();
+        async move {
+            match reply.await {
+                Ok(Ok(StandardMessage::Subscribed(_))) => {
+                    Ok(Subscription::new(client, id, topic, policy, rx))
+                }
+                other => {
+                    let mut state = client.state.lock().unwrap();
+                    state.remove_subscriber(&topic, policy, id);
+                    Err(unexpected_reply(other))
+                }
+            }
+        }
+    }
+
+    pub(super) fn unsubscribe(
+        &self,
+        subscriber: u64,
+        topic: Uri,
+        policy: MatchPolicy,
+    ) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        V: From<String>,
+    {
+        let last = self
+            .state
+            .lock()
+            .unwrap()
+            .remove_subscriber(&topic, policy, subscriber);
+        // Other subscriptions to the same topic keep the remote one alive.
+        let reply = if last {
+            Some(self.request(|id| {
+                let meta = match_policy_meta(policy);
+                BusMessage::new(UnsubscribeMessage::new(id, topic, meta))
+            }))
+        } else {
+            None
+        };
+        async move {
+            match reply {
+                Some(reply) => match reply.await {
+                    Ok(Ok(StandardMessage::Unsubscribed(_))) => Ok(()),
+                    other => Err(unexpected_reply(other)),
+                },
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Publishes an event to a topic.
+    #[inline]
+    pub fn publish(&self, topic: &Uri, body: V) -> Result<(), Error<V>> {
+        self.publish_with_meta(topic, body, Meta::default())
+    }
+
+    /// Publishes an event with meta to a topic.
+    ///
+    /// Publishing is fire-and-forget. An error is only returned if the
+    /// connection is closed.
+    pub fn publish_with_meta(&self, topic: &Uri, body: V, meta: Meta<V>) -> Result<(), Error<V>> {
+        if !self.state.lock().unwrap().is_open() {
+            return Err(Error::Closed);
+        }
+        let message = PublishMessage::new(topic.clone(), Body::new(body), meta);
+        self.tx
+            .unbounded_send(BusMessage::new(message))
+            .map_err(|_| Error::Closed)
+    }
+
+    fn request<F>(&self, f: F) -> oneshot::Receiver<Reply<V>>
+    where
+        F: FnOnce(Id) -> BusMessage<V>,
+    {
+        let (tx, rx) = oneshot::channel();
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_request_id();
+        if state.is_open() && self.tx.unbounded_send(f(id)).is_ok() {
+            state.pending.insert(u64::from(id), tx);
+        }
+        rx
+    }
+}
+
+impl<V> Clone for Client<V> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<V> RpcClient<V> for Client<V> {
+    type Future = ResponseFuture<V>;
+
+    fn call_with_meta(&mut self, procedure: &Uri, body: V, meta: Meta<V>) -> Self::Future {
+        let inner = self.request(|id| {
+            BusMessage::new(CallMessage::new(
+                id,
+                procedure.clone(),
+                Body::new(body),
+                meta,
+            ))
+        });
+        ResponseFuture { inner }
+    }
+}
+
+enum Action<V> {
+    None,
+    /// Reply with the output of the future.
+    Reply(BoxFuture<'static, BusMessage<V>>),
+    /// Close the connection.
+    Close,
+}
+
+/// Handles a message from the remote peer.
+fn handle<V>(state: &Mutex<ClientState<V>>, message: BusMessage<V>) -> Action<V>
+where
+    V: MetaValue + Send + 'static,
+{
+    let message = match message.into_standard() {
+        Ok(message) => message,
+        Err(_) => return Action::None,
+    };
+    let mut state = state.lock().unwrap();
+    if state.session.recv(&message).is_err() {
+        return Action::Close;
+    }
+    match message {
+        StandardMessage::Hello(_) => {
+            state.establish();
+            Action::None
+        }
+        // Either the reply to our goodbye, or the remote peer is closing
+        // the session.
+        StandardMessage::Goodbye(_) => Action::Close,
+        StandardMessage::Call(m) => match state.handler(&m.uri) {
+            Some(handler) => Action::Reply(invoke(handler, m).boxed()),
+            None => {
+                let error = ErrorMessage::new(
+                    m.id,
+                    uris::ERROR_NO_SUCH_PROCEDURE_URI.clone(),
+                    Body::new(V::from(format!("no handler for `{}`", m.uri))),
+                    Meta::default(),
+                );
+                Action::Reply(future::ready(BusMessage::new(error)).boxed())
+            }
+        },
+        StandardMessage::Error(m) => {
+            let error = RemoteError::new(m.uri, m.body.into_inner(), m.meta);
+            state.respond(m.id, Err(Error::Remote(error)));
+            Action::None
+        }
+        StandardMessage::Result(m) => {
+            let id = m.id;
+            state.respond(id, Ok(StandardMessage::Result(m)));
+            Action::None
+        }
+        StandardMessage::Registered(m) => {
+            let id = m.id;
+            state.respond(id, Ok(StandardMessage::Registered(m)));
+            Action::None
+        }
+        StandardMessage::Unregistered(m) => {
+            let id = m.id;
+            state.respond(id, Ok(StandardMessage::Unregistered(m)));
+            Action::None
+        }
+        StandardMessage::Subscribed(m) => {
+            let id = m.id;
+            state.respond(id, Ok(StandardMessage::Subscribed(m)));
+            Action::None
+        }
+        StandardMessage::Unsubscribed(m) => {
+            let id = m.id;
+            state.respond(id, Ok(StandardMessage::Unsubscribed(m)));
+            Action::None
+        }
+        StandardMessage::Event(m) => {
+            state.deliver(m);
+            Action::None
+        }
+        _ => Action::None,
+    }
+}
+
+async fn invoke<V>(handler: BoxHandler<V>, call: CallMessage<Map<V>, V>) -> BusMessage<V> {
+    match handler(call.uri, call.body, call.meta).await {
+        Ok((body, meta)) => BusMessage::new(ResultMessage::new(call.id, Body::new(body), meta)),
+        Err(err) => {
+            let (uri, body, meta) = err.into_parts();
+            BusMessage::new(ErrorMessage::new(call.id, uri, Body::new(body), meta))
+        }
+    }
+}
+
+fn unexpected_reply<V>(reply: Result<Reply<V>, oneshot::Canceled>) -> Error<V> {
+    match reply {
+        Ok(Ok(message)) => Error::UnexpectedReply(message.kind()),
+        Ok(Err(err)) => err,
+        Err(oneshot::Canceled) => Error::Closed,
+    }
+}
+
+/// Future returned by a [`Client`] call.
+pub struct ResponseFuture<V> {
+    inner: oneshot::Receiver<Reply<V>>,
+}
+
+impl<V> Future for ResponseFuture<V> {
+    type Output = Result<(V, Meta<V>), Error<V>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match Pin::new(&mut self.inner).poll(cx) {
+            Poll::Ready(Ok(Ok(StandardMessage::Result(m)))) => {
+                Poll::Ready(Ok((m.body.into_inner(), m.meta)))
+            }
+            Poll::Ready(reply) => Poll::Ready(Err(unexpected_reply(reply))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::{future, SinkExt};
+
+    use super::*;
+    use crate::bus::transport::channel;
+    use crate::bus::Hub;
+    use crate::codec::json::Value;
+    use crate::uris;
+
+    fn details() -> SessionDetails<Value> {
+        SessionDetails::new(Value::Null, Meta::default())
+    }
+
+    #[test]
+    fn test_client_call() {
+        let (transport, mut remote) = channel();
+        let (mut client, connection) = Client::<Value>::new(transport, details());
+        let procedure = Uri::from_static("test.echo").unwrap();
+
+        let peer = async move {
+            match remote.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Hello(_) => (),
+                other => panic!("unexpected message {:?}", other),
+            }
+            let hello = HelloMessage::new(Body::new(Value::from("remote")), Meta::default());
+            remote.send(BusMessage::new(hello)).await.unwrap();
+            for _ in 0..2 {
+                let call = match remote.next().await.unwrap().into_standard().unwrap() {
+                    StandardMessage::Call(m) => m,
+                    other => panic!("unexpected message {:?}", other),
+                };
+                let reply = if call.body.as_inner() == &Value::Null {
+                    BusMessage::new(ErrorMessage::new(
+                        call.id,
+                        uris::ERROR_NO_SUCH_PROCEDURE_URI.clone(),
+                        call.body,
+                        Meta::default(),
+                    ))
+                } else {
+                    BusMessage::new(ResultMessage::new(call.id, call.body, call.meta))
+                };
+                remote.send(reply).await.unwrap();
+            }
+        };
+
+        let calls = async move {
+            let session = client.session().await.unwrap();
+            assert_eq!(session.body(), &Value::from("remote"));
+
+            let (body, _) = client.call(&procedure, Value::from("hi")).await.unwrap();
+            assert_eq!(body, Value::from("hi"));
+
+            match client.call(&procedure, Value::Null).await {
+                Err(Error::Remote(err)) => {
+                    assert_eq!(err.uri(), &uris::ERROR_NO_SUCH_PROCEDURE_URI);
+                    assert_eq!(err.body(), &Value::Null);
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+
+        block_on(future::join3(connection, peer, calls));
+    }
+
+    #[test]
+    fn test_client_unregister_refused_keeps_handler() {
+        let (transport, mut remote) = channel();
+        let (client, connection) = Client::<Value>::new(transport, details());
+        let procedure = Uri::from_static("test.echo").unwrap();
+        let (unregistered_tx, unregistered_rx) = oneshot::channel();
+        let mut unregistered_rx = Some(unregistered_rx);
+
+        let peer = async move {
+            match remote.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Hello(_) => (),
+                other => panic!("unexpected message {:?}", other),
+            }
+            let hello = HelloMessage::new(Body::new(Value::from("remote")), Meta::default());
+            remote.send(BusMessage::new(hello)).await.unwrap();
+            let register = match remote.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Register(m) => m,
+                other => panic!("unexpected message {:?}", other),
+            };
+            let registered = RegisteredMessage::new(register.id, Meta::default());
+            remote.send(BusMessage::new(registered)).await.unwrap();
+
+            // The first unregister is refused, keeping the handler, and the
+            // second accepted, removing it.
+            for refuse in &[true, false] {
+                let unregister = match remote.next().await.unwrap().into_standard().unwrap() {
+                    StandardMessage::Unregister(m) => m,
+                    other => panic!("unexpected message {:?}", other),
+                };
+                let call = CallMessage::new(
+                    Id::new(1),
+                    register.uri.clone(),
+                    Body::new(Value::from("hi")),
+                    Meta::default(),
+                );
+                if *refuse {
+                    let error = ErrorMessage::new(
+                        unregister.id,
+                        uris::ERROR_NO_SUCH_REGISTRATION_URI.clone(),
+                        Body::new(Value::Null),
+                        Meta::default(),
+                    );
+                    remote.send(BusMessage::new(error)).await.unwrap();
+                    remote.send(BusMessage::new(call)).await.unwrap();
+                    match remote.next().await.unwrap().into_standard().unwrap() {
+                        StandardMessage::Result(m) => assert_eq!(m.body.as_inner(), "hi"),
+                        other => panic!("unexpected message {:?}", other),
+                    }
+                } else {
+                    let unregistered = UnregisteredMessage::new(unregister.id, Meta::default());
+                    remote.send(BusMessage::new(unregistered)).await.unwrap();
+                    unregistered_rx.take().unwrap().await.unwrap();
+                    remote.send(BusMessage::new(call)).await.unwrap();
+                    match remote.next().await.unwrap().into_standard().unwrap() {
+                        StandardMessage::Error(m) => {
+                            assert_eq!(m.uri, uris::ERROR_NO_SUCH_PROCEDURE_URI.clone())
+                        }
+                        other => panic!("unexpected message {:?}", other),
+                    }
+                }
+            }
+        };
+
+        let requests = async move {
+            let echo = |_, body: Body<Value>, meta| async move { Ok((body.into_inner(), meta)) };
+            client.register(procedure.clone(), echo).await.unwrap();
+            match client.unregister(&procedure).await {
+                Err(Error::Remote(err)) => {
+                    assert_eq!(err.uri(), &uris::ERROR_NO_SUCH_REGISTRATION_URI)
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+            client.unregister(&procedure).await.unwrap();
+            unregistered_tx.send(()).unwrap();
+            // The connection stays open for the last call.
+            client
+        };
+
+        block_on(future::join3(connection, peer, requests));
+    }
+
+    #[test]
+    fn test_client_call_closed() {
+        let (transport, remote) = channel();
+        let (mut client, connection) = Client::<Value>::new(transport, details());
+        let procedure = Uri::from_static("test.echo").unwrap();
+        drop(remote);
+
+        let call = client.call(&procedure, Value::Null);
+        let (_, response) = block_on(future::join(connection, call));
+        assert!(matches!(response, Err(Error::Closed)));
+    }
+
+    #[test]
+    fn test_client_subscribe_via_hub() {
+        let hub = Hub::<Value>::new();
+        let (publisher_transport, publisher_remote) = channel();
+        let (subscriber_transport, subscriber_remote) = channel();
+        let (publisher, publisher_connection) =
+            Client::<Value>::new(publisher_transport, details());
+        let (subscriber, subscriber_connection) =
+            Client::<Value>::new(subscriber_transport, details());
+
+        let topic = Uri::from_static("test.news.sport").unwrap();
+        let wildcard = Uri::from_static("test.news.*").unwrap();
+
+        let peers = async move {
+            let mut exact = subscriber.subscribe(topic.clone()).await.unwrap();
+            let mut any = subscriber.subscribe(wildcard).await.unwrap();
+
+            publisher.publish(&topic, Value::from(1)).unwrap();
+            let event = exact.next().await.unwrap();
+            assert_eq!(event.topic(), &topic);
+            assert_eq!(event.body(), &Value::from(1));
+            assert_eq!(any.next().await.unwrap().body(), &Value::from(1));
+
+            exact.unsubscribe().await.unwrap();
+            publisher.publish(&topic, Value::from(2)).unwrap();
+            assert_eq!(any.next().await.unwrap().body(), &Value::from(2));
+
+            publisher.close().unwrap();
+            assert!(publisher.publish(&topic, Value::from(3)).is_err());
+        };
+
+        block_on(future::join(
+            future::join(hub.serve(publisher_remote), hub.serve(subscriber_remote)),
+            future::join3(publisher_connection, subscriber_connection, peers),
+        ));
+    }
+
+    #[test]
+    fn test_client_register_via_hub() {
+        let hub = Hub::<Value>::new();
+        let (caller_transport, caller_remote) = channel();
+        let (callee_transport, callee_remote) = channel();
+        let (mut caller, caller_connection) = Client::<Value>::new(caller_transport, details());
+        let (callee, callee_connection) = Client::<Value>::new(callee_transport, details());
+
+        let exact = Uri::from_static("test.math.double").unwrap();
+        let wildcard = Uri::from_static("test.*.name").unwrap();
+        let concrete = Uri::from_static("test.math.name").unwrap();
+
+        let peers = async move {
+            let double = |_, body: Body<Value>, meta| async move {
+                let n = body.as_inner().as_u64().unwrap();
+                Ok((Value::from(n * 2), meta))
+            };
+            let name = |uri: Uri, _, meta| async move { Ok((Value::from(uri.as_str()), meta)) };
+            callee.register(exact.clone(), double).await.unwrap();
+            callee.register(wildcard.clone(), name).await.unwrap();
+
+            let (body, _) = caller.call(&exact, Value::from(21)).await.unwrap();
+            assert_eq!(body, Value::from(42));
+
+            let (body, _) = caller.call(&concrete, Value::Null).await.unwrap();
+            assert_eq!(body, Value::from("test.math.name"));
+
+            callee.unregister(&exact).await.unwrap();
+            match caller.call(&exact, Value::from(21)).await {
+                Err(Error::Remote(err)) => {
+                    assert_eq!(err.uri(), &uris::ERROR_NO_SUCH_PROCEDURE_URI)
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+
+        block_on(future::join(
+            future::join(hub.serve(caller_remote), hub.serve(callee_remote)),
+            future::join3(caller_connection, callee_connection, peers),
+        ));
+    }
+
+    #[test]
+    fn test_client_register_twice_keeps_handler() {
+        let hub = Hub::<Value>::new();
+        let (caller_transport, caller_remote) = channel();
+        let (callee_transport, callee_remote) = channel();
+        let (mut caller, caller_connection) = Client::<Value>::new(caller_transport, details());
+        let (callee, callee_connection) = Client::<Value>::new(callee_transport, details());
+
+        let procedure = Uri::from_static("test.echo").unwrap();
+
+        let peers = async move {
+            let echo = |_, body: Body<Value>, meta| async move { Ok((body.into_inner(), meta)) };
+            let null = |_, _, meta| async move { Ok((Value::Null, meta)) };
+            callee.register(procedure.clone(), echo).await.unwrap();
+            match callee.register(procedure.clone(), null).await {
+                Err(Error::Remote(err)) => {
+                    assert_eq!(err.uri(), &uris::ERROR_PROCEDURE_ALREADY_EXISTS_URI)
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+
+            let (body, _) = caller.call(&procedure, Value::from(1)).await.unwrap();
+            assert_eq!(body, Value::from(1));
+        };
+
+        block_on(future::join(
+            future::join(hub.serve(caller_remote), hub.serve(callee_remote)),
+            future::join3(caller_connection, callee_connection, peers),
+        ));
+    }
+}
@@ -1,28 +1,786 @@
-use std::marker::PhantomData;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::future::{self, Either};
+use futures::{pin_mut, StreamExt};
+
+use super::message::BusMessage;
+use super::read_match_policy;
+use super::session::{Session, SessionDetails, SessionError};
 use super::transport::Transport;
+use super::version::{Capabilities, MetaValue, NegotiatedVersion};
+use crate::codec::generic::Map;
+use crate::message::*;
+use crate::types::{Body, Id, InternedUri, MatchPolicy, Meta, Uri, UriInterner, UriTrie};
+use crate::uris;
+
+/// Identifies a session within a hub.
+pub type SessionId = u64;
+
+struct Invocation {
+    caller: SessionId,
+    request: Id,
+    callee: SessionId,
+}
+
+/// A registration or subscription of a session.
+struct Route {
+    session: SessionId,
+    /// Keeps the URI interned for as long as the route exists.
+    _uri: Option<InternedUri>,
+}
+
+struct Peer<V> {
+    tx: UnboundedSender<BusMessage<V>>,
+    session: Session<V>,
+}
+
+struct HubState<V> {
+    capabilities: Option<Capabilities>,
+    interner: Option<Arc<UriInterner>>,
+    next_session_id: SessionId,
+    next_invocation_id: u64,
+    sessions: HashMap<SessionId, Peer<V>>,
+    /// The session registered for each procedure.
+    registrations: UriTrie<Route>,
+    /// The sessions subscribed to each topic.
+    subscriptions: UriTrie<Route>,
+    invocations: HashMap<u64, Invocation>,
+}
 
-struct HubInner<T, V> {
-    value: PhantomData<V>,
-    transport: T,
+struct HubInner<V> {
+    state: Mutex<HubState<V>>,
 }
 
-pub struct Hub<T, V> {
-    inner: Arc<HubInner<T, V>>,
+/// A message router between many sessions.
+///
+/// Each transport served by the hub becomes a session, which is
+/// established once the peer says hello; the hub replies with the same
+/// details. A peer sending a message not allowed in the current stage of
+/// its session is sent a protocol violation goodbye and closed. A hub
+/// constructed with [`Hub::with_capabilities`] negotiates a spec version
+/// with each peer, replying with the agreed version and features, and
+/// says goodbye to peers without a version in common. Calls are routed
+/// to the session that registered the procedure, and publications are
+/// delivered to every session with a subscription matching the topic.
+///
+/// Registrations and subscriptions are matched under the [`MatchPolicy`]
+/// given in their meta with the [`MATCH_KEY`](super::MATCH_KEY), which
+/// defaults to wildcard matching. When several registrations match a
+/// call, an exact registration takes precedence over a prefix one, and a
+/// prefix one over a wildcard one, as ordered by [`UriTrie::matches`].
+///
+/// A hub given a [`UriInterner`] with [`Hub::with_interner`] interns the
+/// URIs of the registrations and subscriptions it keeps, so they share
+/// contents with every other use of the same URI rather than holding onto
+/// the message they arrived in. A URI stays interned until its last
+/// registration or subscription is removed. Routing still walks the
+/// segments of the URIs, so interning saves memory, not comparisons.
+pub struct Hub<V> {
+    inner: Arc<HubInner<V>>,
 }
 
-impl<T, V> Hub<T, V>
+impl<V> Hub<V>
 where
-    T: Transport<V>,
+    V: MetaValue,
 {
-    pub fn new(transport: T) -> Self {
+    pub fn new() -> Self {
+        Self::with_optional_capabilities(None)
+    }
+
+    /// Constructs a hub that negotiates a version with each peer.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self::with_optional_capabilities(Some(capabilities))
+    }
+
+    fn with_optional_capabilities(capabilities: Option<Capabilities>) -> Self {
+        let state = HubState {
+            capabilities,
+            interner: None,
+            next_session_id: 1,
+            next_invocation_id: 1,
+            sessions: HashMap::new(),
+            registrations: UriTrie::new(),
+            subscriptions: UriTrie::new(),
+            invocations: HashMap::new(),
+        };
         let inner = HubInner {
-            transport,
-            value: PhantomData,
+            state: Mutex::new(state),
         };
         Self {
             inner: Arc::new(inner),
         }
     }
+
+    /// Interns the URIs registered and subscribed to with the given
+    /// interner, such as [`UriInterner::global`].
+    pub fn with_interner(self, interner: Arc<UriInterner>) -> Self {
+        self.state().interner = Some(interner);
+        self
+    }
+
+    /// Returns the number of open sessions.
+    pub fn session_count(&self) -> usize {
+        self.state().sessions.len()
+    }
+
+    /// Returns the details a session said hello with, if established.
+    pub fn session_details(&self, session: SessionId) -> Option<SessionDetails<V>> {
+        let state = self.state();
+        let peer = state.sessions.get(&session)?;
+        if peer.session.is_established() {
+            peer.session.peer().cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the version and features agreed on with a session, if
+    /// negotiated.
+    pub fn session_negotiated(&self, session: SessionId) -> Option<NegotiatedVersion> {
+        let state = self.state();
+        let peer = state.sessions.get(&session)?;
+        peer.session.negotiated().cloned()
+    }
+
+    /// Serves a session over the given transport.
+    ///
+    /// The returned future completes when the transport is closed by
+    /// the peer, the peer says goodbye, or writing to the transport fails.
+    pub async fn serve<T>(&self, transport: T)
+    where
+        T: Transport<V>,
+    {
+        let (sink, mut stream) = transport.split();
+        let (tx, rx) = mpsc::unbounded();
+        let session = self.state().open_session(tx);
+
+        let reader = async {
+            while let Some(message) = stream.next().await {
+                if !self.handle(session, message) {
+                    break;
+                }
+            }
+        };
+        let writer = rx.map(Ok).forward(sink);
+
+        pin_mut!(reader, writer);
+
+        match future::select(reader, writer).await {
+            Either::Left(((), writer)) => {
+                self.state().close_session(session);
+                // Flush whatever is left for the peer.
+                let _ = writer.await;
+            }
+            Either::Right(_) => {
+                self.state().close_session(session);
+            }
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, HubState<V>> {
+        self.inner.state.lock().unwrap()
+    }
+
+    /// Handles a message from a session, returning `false` if the
+    /// session should be closed.
+    fn handle(&self, session: SessionId, message: BusMessage<V>) -> bool {
+        let message = match message.into_standard() {
+            Ok(message) => message,
+            Err(_) => return true,
+        };
+        let mut state = self.state();
+        if let Err(err) = state.recv(session, &message) {
+            state.abort(session, err);
+            return false;
+        }
+        match message {
+            StandardMessage::Hello(m) => {
+                let mut meta = m.meta;
+                if let Some(negotiated) = state.negotiated(session) {
+                    negotiated.to_capabilities().write_meta(&mut meta);
+                }
+                state.send(session, HelloMessage::new(m.body, meta));
+            }
+            StandardMessage::Goodbye(_) => {
+                let reply = GoodbyeMessage::new(uris::CLOSE_NORMAL_URI.clone(), Meta::default());
+                state.send(session, reply);
+                return false;
+            }
+            StandardMessage::Call(m) => state.call(session, m),
+            StandardMessage::Result(m) => {
+                if let Some(invocation) = state.take_invocation(session, m.id) {
+                    let reply = ResultMessage::new(invocation.request, m.body, m.meta);
+                    state.send(invocation.caller, reply);
+                }
+            }
+            StandardMessage::Error(m) => {
+                if let Some(invocation) = state.take_invocation(session, m.id) {
+                    let reply = ErrorMessage::new(invocation.request, m.uri, m.body, m.meta);
+                    state.send(invocation.caller, reply);
+                }
+            }
+            StandardMessage::Register(m) => state.register(session, m),
+            StandardMessage::Unregister(m) => state.unregister(session, m),
+            StandardMessage::Subscribe(m) => state.subscribe(session, m),
+            StandardMessage::Unsubscribe(m) => state.unsubscribe(session, m),
+            StandardMessage::Publish(m) => state.publish(m),
+            // Messages only ever sent by a hub are ignored.
+            _ => (),
+        }
+        true
+    }
+}
+
+impl<V> Clone for Hub<V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<V> Default for Hub<V>
+where
+    V: MetaValue,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> HubState<V>
+where
+    V: MetaValue,
+{
+    fn open_session(&mut self, tx: UnboundedSender<BusMessage<V>>) -> SessionId {
+        let session = self.next_session_id;
+        self.next_session_id += 1;
+        let peer = Peer {
+            tx,
+            session: match &self.capabilities {
+                Some(capabilities) => Session::with_capabilities(capabilities.clone()),
+                None => Session::new(),
+            },
+        };
+        self.sessions.insert(session, peer);
+        session
+    }
+
+    fn close_session(&mut self, session: SessionId) {
+        self.sessions.remove(&session);
+        self.registrations.retain(|r| r.session != session);
+        self.subscriptions.retain(|r| r.session != session);
+
+        let canceled: Vec<_> = self
+            .invocations
+            .iter()
+            .filter(|(_, i)| i.caller == session || i.callee == session)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in canceled {
+            let invocation = self.invocations.remove(&id).unwrap();
+            if invocation.caller != session {
+                let reply = error_message(
+                    invocation.request,
+                    &uris::ERROR_CANCELED_URI,
+                    "callee session closed".into(),
+                );
+                self.send(invocation.caller, reply);
+            }
+        }
+    }
+
+    fn recv(
+        &mut self,
+        session: SessionId,
+        message: &StandardMessage<Map<V>, V>,
+    ) -> Result<(), SessionError> {
+        match self.sessions.get_mut(&session) {
+            Some(peer) => peer.session.recv(message),
+            None => Ok(()),
+        }
+    }
+
+    fn negotiated(&self, session: SessionId) -> Option<NegotiatedVersion> {
+        let peer = self.sessions.get(&session)?;
+        peer.session.negotiated().cloned()
+    }
+
+    fn send<M>(&mut self, session: SessionId, message: M)
+    where
+        M: Message<Map<V>, V>,
+    {
+        if let Some(peer) = self.sessions.get_mut(&session) {
+            // Messages no longer valid for the session are dropped.
+            if peer.session.send(message.kind()).is_ok() {
+                // The session is closing if this fails.
+                let _ = peer.tx.unbounded_send(BusMessage::new(message));
+            }
+        }
+    }
+
+    /// Says goodbye to a session that violated the protocol or shares no
+    /// version with the hub.
+    fn abort(&mut self, session: SessionId, err: SessionError) {
+        if let Some(peer) = self.sessions.get_mut(&session) {
+            peer.session.abort();
+            let mut meta = Map::default();
+            meta.insert("reason".into(), V::from(err.to_string()));
+            let goodbye = GoodbyeMessage::<Map<V>, V>::new(err.uri().clone(), Meta::new(meta));
+            let _ = peer.tx.unbounded_send(BusMessage::new(goodbye));
+        }
+    }
+
+    fn take_invocation(&mut self, callee: SessionId, id: Id) -> Option<Invocation> {
+        let id = u64::from(id);
+        match self.invocations.get(&id) {
+            Some(invocation) if invocation.callee == callee => self.invocations.remove(&id),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, caller: SessionId, m: CallMessage<Map<V>, V>) {
+        let callee = self
+            .registrations
+            .matches(&m.uri)
+            .next()
+            .map(|(_, route)| route.session);
+
+        match callee {
+            Some(callee) => {
+                let id = self.next_invocation_id;
+                self.next_invocation_id += 1;
+                self.invocations.insert(
+                    id,
+                    Invocation {
+                        caller,
+                        request: m.id,
+                        callee,
+                    },
+                );
+                self.send(callee, CallMessage::new(Id::new(id), m.uri, m.body, m.meta));
+            }
+            None => {
+                let desc = format!("no procedure registered for `{}`", m.uri);
+                let reply = error_message(m.id, &uris::ERROR_NO_SUCH_PROCEDURE_URI, desc);
+                self.send(caller, reply);
+            }
+        }
+    }
+
+    /// Returns the route of a session to a URI, interning the URI if the
+    /// hub has an interner.
+    fn route(&self, session: SessionId, uri: Uri) -> (Uri, Route) {
+        let interned = self.interner.as_ref().map(|i| i.intern(&uri));
+        let uri = interned.as_ref().map_or(uri, InternedUri::to_uri);
+        let route = Route {
+            session,
+            _uri: interned,
+        };
+        (uri, route)
+    }
+
+    /// Reads the match policy of a request, replying with an error if the
+    /// policy is unknown.
+    fn match_policy(
+        &mut self,
+        session: SessionId,
+        id: Id,
+        meta: &Meta<Map<V>, V>,
+    ) -> Option<MatchPolicy> {
+        match read_match_policy(meta) {
+            Ok(policy) => Some(policy),
+            Err(err) => {
+                let reply = error_message(id, &uris::ERROR_PROTOCOL_VIOLATION_URI, err.to_string());
+                self.send(session, reply);
+                None
+            }
+        }
+    }
+
+    fn register(&mut self, session: SessionId, m: RegisterMessage<Map<V>, V>) {
+        let policy = match self.match_policy(session, m.id, &m.meta) {
+            Some(policy) => policy,
+            None => return,
+        };
+        if !self.registrations.get(&m.uri, policy).is_empty() {
+            let desc = format!("procedure `{}` is already registered", m.uri);
+            let reply = error_message(m.id, &uris::ERROR_PROCEDURE_ALREADY_EXISTS_URI, desc);
+            self.send(session, reply);
+            return;
+        }
+        let (uri, route) = self.route(session, m.uri);
+        self.registrations.insert(uri, policy, route);
+        self.send(session, RegisteredMessage::new(m.id, Meta::default()));
+    }
+
+    fn unregister(&mut self, session: SessionId, m: UnregisterMessage<Map<V>, V>) {
+        let policy = match self.match_policy(session, m.id, &m.meta) {
+            Some(policy) => policy,
+            None => return,
+        };
+        let removed = self
+            .registrations
+            .remove_if(&m.uri, policy, |r| r.session == session);
+
+        match removed {
+            Some(_) => {
+                self.send(session, UnregisteredMessage::new(m.id, Meta::default()));
+            }
+            None => {
+                let desc = format!("procedure `{}` is not registered", m.uri);
+                let reply = error_message(m.id, &uris::ERROR_NO_SUCH_REGISTRATION_URI, desc);
+                self.send(session, reply);
+            }
+        }
+    }
+
+    fn subscribe(&mut self, session: SessionId, m: SubscribeMessage<Map<V>, V>) {
+        let policy = match self.match_policy(session, m.id, &m.meta) {
+            Some(policy) => policy,
+            None => return,
+        };
+        let routes = self.subscriptions.get(&m.uri, policy);
+        if !routes.iter().any(|r| r.session == session) {
+            let (uri, route) = self.route(session, m.uri);
+            self.subscriptions.insert(uri, policy, route);
+        }
+        self.send(session, SubscribedMessage::new(m.id, Meta::default()));
+    }
+
+    fn unsubscribe(&mut self, session: SessionId, m: UnsubscribeMessage<Map<V>, V>) {
+        let policy = match self.match_policy(session, m.id, &m.meta) {
+            Some(policy) => policy,
+            None => return,
+        };
+        let removed = self
+            .subscriptions
+            .remove_if(&m.uri, policy, |r| r.session == session);
+
+        match removed {
+            Some(_) => {
+                self.send(session, UnsubscribedMessage::new(m.id, Meta::default()));
+            }
+            None => {
+                let desc = format!("not subscribed to `{}`", m.uri);
+                let reply = error_message(m.id, &uris::ERROR_NO_SUCH_SUBSCRIPTION_URI, desc);
+                self.send(session, reply);
+            }
+        }
+    }
+
+    fn publish(&mut self, m: PublishMessage<Map<V>, V>) {
+        let mut sessions: Vec<_> = self
+            .subscriptions
+            .matches(&m.uri)
+            .map(|(_, route)| route.session)
+            .collect();
+
+        // A session receives each event once, regardless of how many of
+        // its subscriptions match.
+        sessions.sort_unstable();
+        sessions.dedup();
+
+        let body = m.body.into_inner();
+        let meta = m.meta.into_inner();
+        for session in sessions {
+            let event = EventMessage::new(
+                m.uri.clone(),
+                Body::new(body.clone()),
+                Meta::new(meta.clone()),
+            );
+            self.send(session, event);
+        }
+    }
+}
+
+fn error_message<V>(request: Id, error: &Uri, desc: String) -> ErrorMessage<Map<V>, V>
+where
+    V: From<String>,
+{
+    ErrorMessage::new(
+        request,
+        error.clone(),
+        Body::new(V::from(desc)),
+        Meta::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::{future, SinkExt};
+
+    use super::*;
+    use crate::bus::transport::{channel, ChannelTransport};
+    use crate::codec::json::Value;
+
+    fn procedure() -> Uri {
+        Uri::from_static("test.echo").unwrap()
+    }
+
+    async fn request(
+        transport: &mut ChannelTransport<Value>,
+        message: impl Message<Map<Value>, Value>,
+    ) -> StandardMessage<Map<Value>, Value> {
+        transport.send(BusMessage::new(message)).await.unwrap();
+        transport.next().await.unwrap().into_standard().unwrap()
+    }
+
+    async fn hello(transport: &mut ChannelTransport<Value>) {
+        let hello = HelloMessage::new(Body::new(Value::Null), Meta::default());
+        let reply = request(transport, hello).await;
+        assert!(matches!(reply, StandardMessage::Hello(_)));
+    }
+
+    #[test]
+    fn test_hub_routes_call_to_callee() {
+        let hub = Hub::<Value>::new();
+        let (mut caller, caller_remote) = channel();
+        let (mut callee, callee_remote) = channel();
+
+        let peers = async move {
+            hello(&mut caller).await;
+            hello(&mut callee).await;
+            let reply = request(
+                &mut callee,
+                RegisterMessage::new(Id::new(1), procedure(), Meta::default()),
+            )
+            .await;
+            assert!(matches!(reply, StandardMessage::Registered(_)));
+
+            let call = CallMessage::new(
+                Id::new(7),
+                procedure(),
+                Body::new(Value::from("hello")),
+                Meta::default(),
+            );
+            caller.send(BusMessage::new(call)).await.unwrap();
+
+            let invocation = match callee.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Call(m) => m,
+                other => panic!("unexpected message {:?}", other),
+            };
+            let result = ResultMessage::new(invocation.id, invocation.body, Meta::default());
+            callee.send(BusMessage::new(result)).await.unwrap();
+
+            match caller.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Result(m) => {
+                    assert_eq!(u64::from(m.id), 7);
+                    assert_eq!(m.body.into_inner(), Value::from("hello"));
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+        };
+
+        block_on(future::join3(
+            hub.serve(caller_remote),
+            hub.serve(callee_remote),
+            peers,
+        ));
+        assert_eq!(hub.session_count(), 0);
+    }
+
+    #[test]
+    fn test_hub_routes_call_by_match_policy() {
+        let hub = Hub::<Value>::new();
+        let (mut caller, caller_remote) = channel();
+        let (mut prefix_callee, prefix_remote) = channel();
+        let (mut wildcard_callee, wildcard_remote) = channel();
+
+        let peers = async move {
+            hello(&mut caller).await;
+            hello(&mut prefix_callee).await;
+            hello(&mut wildcard_callee).await;
+            let register = |id, uri, policy| {
+                let uri = Uri::from_static(uri).unwrap();
+                RegisterMessage::new(Id::new(id), uri, crate::bus::match_policy_meta(policy))
+            };
+            let reply = request(
+                &mut wildcard_callee,
+                register(1, "test.*", MatchPolicy::Wildcard),
+            )
+            .await;
+            assert!(matches!(reply, StandardMessage::Registered(_)));
+            let reply = request(
+                &mut prefix_callee,
+                register(1, "test.", MatchPolicy::Prefix),
+            )
+            .await;
+            assert!(matches!(reply, StandardMessage::Registered(_)));
+
+            let mut meta = Meta::<Map<Value>, Value>::default();
+            meta.as_inner_mut()
+                .insert(crate::bus::MATCH_KEY.into(), Value::from("fuzzy"));
+            let reply = request(
+                &mut prefix_callee,
+                RegisterMessage::new(Id::new(2), procedure(), meta),
+            )
+            .await;
+            match reply {
+                StandardMessage::Error(m) => assert_eq!(m.uri, uris::ERROR_PROTOCOL_VIOLATION_URI),
+                other => panic!("unexpected message {:?}", other),
+            }
+
+            // A prefix registration takes precedence over a wildcard one.
+            let call = CallMessage::new(
+                Id::new(7),
+                procedure(),
+                Body::new(Value::Null),
+                Meta::default(),
+            );
+            caller.send(BusMessage::new(call)).await.unwrap();
+            match prefix_callee.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Call(m) => assert_eq!(m.uri, procedure()),
+                other => panic!("unexpected message {:?}", other),
+            }
+        };
+
+        block_on(future::join4(
+            hub.serve(caller_remote),
+            hub.serve(prefix_remote),
+            hub.serve(wildcard_remote),
+            peers,
+        ));
+    }
+
+    #[test]
+    fn test_hub_call_without_callee() {
+        let hub = Hub::<Value>::new();
+        let (mut caller, caller_remote) = channel();
+
+        let peer = async move {
+            hello(&mut caller).await;
+            let call = CallMessage::new(
+                Id::new(1),
+                procedure(),
+                Body::new(Value::Null),
+                Meta::default(),
+            );
+            match request(&mut caller, call).await {
+                StandardMessage::Error(m) => assert_eq!(m.uri, uris::ERROR_NO_SUCH_PROCEDURE_URI),
+                other => panic!("unexpected message {:?}", other),
+            }
+        };
+
+        block_on(future::join(hub.serve(caller_remote), peer));
+    }
+
+    #[test]
+    fn test_hub_rejects_call_before_hello() {
+        let hub = Hub::<Value>::new();
+        let (mut caller, caller_remote) = channel();
+
+        let peer = async move {
+            let call = CallMessage::new(
+                Id::new(1),
+                procedure(),
+                Body::new(Value::Null),
+                Meta::default(),
+            );
+            match request(&mut caller, call).await {
+                StandardMessage::Goodbye(m) => {
+                    assert_eq!(m.uri, uris::ERROR_PROTOCOL_VIOLATION_URI)
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+            // The hub closes the session after saying goodbye.
+            assert!(caller.next().await.is_none());
+        };
+
+        block_on(future::join(hub.serve(caller_remote), peer));
+        assert_eq!(hub.session_count(), 0);
+    }
+
+    #[test]
+    fn test_hub_refuses_unsupported_version() {
+        let version = |v| semver::Version::parse(v).unwrap();
+        let capabilities = Capabilities::new(vec![version("1.0.0"), version("1.1.0")], Vec::new());
+        let hub = Hub::<Value>::with_capabilities(capabilities);
+        let (mut peer, remote) = channel();
+        let (mut old_peer, old_remote) = channel();
+
+        let peers = async move {
+            let mut meta = Meta::default();
+            Capabilities::new(vec![version("1.0.0"), version("2.0.0")], Vec::new())
+                .write_meta(&mut meta);
+            let hello = HelloMessage::new(Body::new(Value::Null), meta);
+            match request(&mut peer, hello).await {
+                StandardMessage::Hello(m) => {
+                    let negotiated = Capabilities::from_meta(&m.meta).unwrap();
+                    assert_eq!(negotiated.versions(), &[version("1.0.0")]);
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+            peer.close().await.unwrap();
+
+            let mut meta = Meta::default();
+            Capabilities::new(vec![version("0.1.0")], Vec::new()).write_meta(&mut meta);
+            let hello = HelloMessage::new(Body::new(Value::Null), meta);
+            match request(&mut old_peer, hello).await {
+                StandardMessage::Goodbye(m) => {
+                    assert_eq!(m.uri, uris::ERROR_UNSUPPORTED_VERSION_URI)
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+        };
+
+        block_on(future::join3(
+            hub.serve(remote),
+            hub.serve(old_remote),
+            peers,
+        ));
+    }
+
+    #[test]
+    fn test_hub_delivers_publications() {
+        let interner = Arc::new(UriInterner::new());
+        let hub = Hub::<Value>::new().with_interner(interner.clone());
+        let (mut publisher, publisher_remote) = channel();
+        let (mut subscriber, subscriber_remote) = channel();
+
+        let peers = async move {
+            hello(&mut publisher).await;
+            hello(&mut subscriber).await;
+            let reply = request(
+                &mut subscriber,
+                SubscribeMessage::new(Id::new(1), procedure(), Meta::default()),
+            )
+            .await;
+            assert!(matches!(reply, StandardMessage::Subscribed(_)));
+
+            let unsubscribed = Uri::from_static("test.unsubscribed").unwrap();
+            for topic in [unsubscribed, procedure()].iter() {
+                let publish =
+                    PublishMessage::new(topic.clone(), Body::new(Value::from(1)), Meta::default());
+                publisher.send(BusMessage::new(publish)).await.unwrap();
+            }
+
+            match subscriber.next().await.unwrap().into_standard().unwrap() {
+                StandardMessage::Event(m) => assert_eq!(m.uri, procedure()),
+                other => panic!("unexpected message {:?}", other),
+            }
+            // Only the subscribed URI is interned, not each one published.
+            assert!(interner.get(&procedure()).is_some());
+            assert_eq!(interner.len(), 1);
+
+            let reply = request(
+                &mut subscriber,
+                UnsubscribeMessage::new(Id::new(2), procedure(), Meta::default()),
+            )
+            .await;
+            assert!(matches!(reply, StandardMessage::Unsubscribed(_)));
+            assert!(interner.is_empty());
+            drop(publisher);
+        };
+
+        block_on(future::join3(
+            hub.serve(publisher_remote),
+            hub.serve(subscriber_remote),
+            peers,
+        ));
+    }
 }
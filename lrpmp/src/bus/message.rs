@@ -1,11 +1,33 @@
 use crate::codec::generic::Map;
-use crate::message::{GenericMessage, Message, MessageDecoder, MessageEncoder, MessageError};
+use crate::message::dec::KindDecoder;
+use crate::message::{
+    GenericMessage, Message, MessageDecoder, MessageEncoder, MessageError, StandardMessage,
+};
 use crate::types::KnownKind;
 
+#[derive(Debug, Clone)]
 pub struct BusMessage<V> {
     inner: GenericMessage<Map<V>, V>,
 }
 
+impl<V> BusMessage<V> {
+    /// Constructs a bus message from any message.
+    pub fn new<M>(message: M) -> Self
+    where
+        M: Message<Map<V>, V>,
+    {
+        Self {
+            inner: message.into_generic(),
+        }
+    }
+}
+
+impl<V> From<GenericMessage<Map<V>, V>> for BusMessage<V> {
+    fn from(inner: GenericMessage<Map<V>, V>) -> Self {
+        Self { inner }
+    }
+}
+
 impl<V> Message<Map<V>, V> for BusMessage<V> {
     fn kind(&self) -> KnownKind {
         self.inner.kind()
@@ -29,9 +51,14 @@ impl<V> Message<Map<V>, V> for BusMessage<V> {
     where
         D: MessageDecoder<Map<V>, V>,
     {
-        Ok(Self {
-            inner: GenericMessage::decode(decoder)?,
-        })
+        let (kind, decoder) = decoder.start()?;
+        let decoder = KindDecoder::new(kind, decoder);
+        match kind {
+            // Decoded as a standard message first so fields keep their types;
+            // a generic decode would read a string body as a plain string.
+            KnownKind::Standard(_) => StandardMessage::decode(decoder).map(Self::new),
+            KnownKind::Custom(_) => GenericMessage::decode(decoder).map(Self::from),
+        }
     }
 
     fn into_generic(self) -> GenericMessage<Map<V>, V> {
@@ -1,22 +1,91 @@
 mod client;
 mod hub;
 mod message;
+mod session;
+mod subscription;
 mod transport;
+mod version;
 
-// pub use self::hub::Hub;
-// pub use self::client::Client;
-// pub use self::transport::*;
-// pub use self::message::BusMessage;
+pub use self::client::{Client, ResponseFuture, RpcClient};
+pub use self::hub::{Hub, SessionId};
+pub use self::message::BusMessage;
+pub use self::session::{ProtocolViolation, Session, SessionDetails, SessionError, Stage};
+pub use self::subscription::{Event, Subscription};
+pub use self::transport::*;
+pub use self::version::*;
 
 use crate::codec::generic::Meta;
-use crate::types::Uri;
+use crate::types::{KnownKind, MatchPolicy, ParseMatchPolicyError, Uri};
 
+#[derive(Debug)]
 pub enum Error<V> {
+    /// The remote peer replied with an error.
     Remote(RemoteError<V>),
+    /// The connection closed before a reply was received.
+    Closed,
+    /// The remote peer replied with an unexpected message kind.
+    UnexpectedReply(KnownKind),
 }
 
+/// An error message received from a remote peer.
+#[derive(Debug)]
 pub struct RemoteError<V> {
     error: Uri,
     body: V,
     meta: Meta<V>,
 }
+
+impl<V> RemoteError<V> {
+    pub fn new(error: Uri, body: V, meta: Meta<V>) -> Self {
+        Self { error, body, meta }
+    }
+
+    /// Returns the error URI.
+    pub fn uri(&self) -> &Uri {
+        &self.error
+    }
+
+    pub fn body(&self) -> &V {
+        &self.body
+    }
+
+    pub fn meta(&self) -> &Meta<V> {
+        &self.meta
+    }
+
+    pub fn into_parts(self) -> (Uri, V, Meta<V>) {
+        (self.error, self.body, self.meta)
+    }
+}
+
+/// The meta key of the [`MatchPolicy`] to register or subscribe with.
+pub const MATCH_KEY: &str = "match";
+
+/// Reads the match policy from the meta of a registration or subscription,
+/// which defaults to [`MatchPolicy::Wildcard`].
+pub(crate) fn read_match_policy<V>(meta: &Meta<V>) -> Result<MatchPolicy, ParseMatchPolicyError>
+where
+    V: MetaValue,
+{
+    match meta
+        .as_inner()
+        .get(MATCH_KEY)
+        .and_then(MetaValue::to_meta_str)
+    {
+        Some(policy) => policy.parse(),
+        None => Ok(MatchPolicy::default()),
+    }
+}
+
+/// Returns the meta to register or subscribe with under a match policy.
+pub(crate) fn match_policy_meta<V>(policy: MatchPolicy) -> Meta<V>
+where
+    V: From<String>,
+{
+    let mut meta = Meta::default();
+    if policy != MatchPolicy::default() {
+        let policy = V::from(policy.to_string());
+        meta.as_inner_mut().insert(MATCH_KEY.into(), policy);
+    }
+    meta
+}
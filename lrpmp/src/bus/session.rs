@@ -0,0 +1,379 @@
+use std::fmt;
+
+use super::version::{Capabilities, MetaValue, NegotiatedVersion, UnsupportedVersion};
+use super::Meta;
+use crate::codec::generic::Map;
+use crate::message::{Message, StandardMessage};
+use crate::types::{KnownKind, StandardKind, Uri};
+use crate::uris;
+
+/// The stage of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Peers are exchanging `HELLO` messages.
+    Handshake,
+    /// Both peers have said hello.
+    Established,
+    /// A peer has said goodbye and is waiting for the reply.
+    Closing,
+    /// Both peers have said goodbye, or the session was aborted.
+    Closed,
+}
+
+impl Stage {
+    /// Returns the name of the stage as used by the spec.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::Established => "established",
+            Self::Closing => "closing",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The details a peer says hello with.
+#[derive(Debug, Clone)]
+pub struct SessionDetails<V> {
+    body: V,
+    meta: Meta<V>,
+}
+
+impl<V> SessionDetails<V> {
+    pub fn new(body: V, meta: Meta<V>) -> Self {
+        Self { body, meta }
+    }
+
+    pub fn body(&self) -> &V {
+        &self.body
+    }
+
+    pub fn meta(&self) -> &Meta<V> {
+        &self.meta
+    }
+
+    pub fn into_parts(self) -> (V, Meta<V>) {
+        (self.body, self.meta)
+    }
+}
+
+/// Error produced when a message is not valid in the current stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolViolation {
+    pub kind: KnownKind,
+    pub stage: Stage,
+}
+
+impl fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "message kind {} not allowed in {} stage",
+            self.kind.code(),
+            self.stage
+        )
+    }
+}
+
+/// Error produced when a received message can't advance the session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionError {
+    ProtocolViolation(ProtocolViolation),
+    /// The peer said hello without a version in common.
+    UnsupportedVersion(UnsupportedVersion),
+}
+
+impl SessionError {
+    /// Returns the standard error URI to refuse the peer with.
+    pub fn uri(&self) -> &'static Uri {
+        match self {
+            Self::ProtocolViolation(_) => &uris::ERROR_PROTOCOL_VIOLATION_URI,
+            Self::UnsupportedVersion(_) => &uris::ERROR_UNSUPPORTED_VERSION_URI,
+        }
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ProtocolViolation(err) => err.fmt(f),
+            Self::UnsupportedVersion(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<ProtocolViolation> for SessionError {
+    fn from(err: ProtocolViolation) -> Self {
+        Self::ProtocolViolation(err)
+    }
+}
+
+/// The state machine of a session from the view of one peer.
+///
+/// Every message sent and received is checked against the stages the
+/// spec allows its kind in. A session is established once both peers have
+/// sent `HELLO`, and closed once both have sent `GOODBYE`.
+///
+/// A session constructed with [`Session::with_capabilities`] also
+/// negotiates a spec version from the peer's `HELLO`. A peer that doesn't
+/// advertise its capabilities is taken to support only the spec version
+/// this crate was compiled against.
+#[derive(Debug)]
+pub struct Session<V> {
+    stage: Stage,
+    hello_sent: bool,
+    peer: Option<SessionDetails<V>>,
+    capabilities: Option<Capabilities>,
+    negotiated: Option<NegotiatedVersion>,
+}
+
+impl<V> Session<V> {
+    pub fn new() -> Self {
+        Self {
+            stage: Stage::Handshake,
+            hello_sent: false,
+            peer: None,
+            capabilities: None,
+            negotiated: None,
+        }
+    }
+
+    /// Constructs a session that negotiates a version with the peer.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self {
+            capabilities: Some(capabilities),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the version and features agreed on with the peer, once it
+    /// has said hello.
+    pub fn negotiated(&self) -> Option<&NegotiatedVersion> {
+        self.negotiated.as_ref()
+    }
+
+    /// Returns the current stage.
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn is_established(&self) -> bool {
+        self.stage == Stage::Established
+    }
+
+    /// Returns the details the peer said hello with, once received.
+    pub fn peer(&self) -> Option<&SessionDetails<V>> {
+        self.peer.as_ref()
+    }
+
+    /// Checks a message kind is allowed in the current stage.
+    pub fn check(&self, kind: KnownKind) -> Result<(), ProtocolViolation> {
+        if kind.stages().contains(&self.stage.name()) {
+            Ok(())
+        } else {
+            Err(ProtocolViolation {
+                kind,
+                stage: self.stage,
+            })
+        }
+    }
+
+    /// Advances the session given a message about to be sent.
+    pub fn send(&mut self, kind: KnownKind) -> Result<(), ProtocolViolation> {
+        self.check(kind)?;
+        if kind == KnownKind::Standard(StandardKind::Hello) {
+            self.hello_sent = true;
+            if self.peer.is_some() {
+                self.stage = Stage::Established;
+            }
+        } else if kind == KnownKind::Standard(StandardKind::Goodbye) {
+            self.said_goodbye();
+        }
+        Ok(())
+    }
+
+    /// Advances the session given a message received from the peer.
+    pub fn recv(&mut self, message: &StandardMessage<Map<V>, V>) -> Result<(), SessionError>
+    where
+        V: MetaValue,
+    {
+        self.check(message.kind())?;
+        match message {
+            StandardMessage::Hello(m) => {
+                if let Some(capabilities) = &self.capabilities {
+                    let peer = Capabilities::from_meta(&m.meta).unwrap_or_default();
+                    let negotiated = capabilities
+                        .negotiate(&peer)
+                        .map_err(SessionError::UnsupportedVersion)?;
+                    self.negotiated = Some(negotiated);
+                }
+                let details = SessionDetails::new(m.body.as_inner().clone(), m.meta.clone());
+                self.peer = Some(details);
+                if self.hello_sent {
+                    self.stage = Stage::Established;
+                }
+            }
+            StandardMessage::Goodbye(_) => self.said_goodbye(),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Closes the session without a goodbye.
+    pub fn abort(&mut self) {
+        self.stage = Stage::Closed;
+    }
+
+    fn said_goodbye(&mut self) {
+        self.stage = match self.stage {
+            Stage::Established => Stage::Closing,
+            _ => Stage::Closed,
+        };
+    }
+}
+
+impl<V> Default for Session<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::json::Value;
+    use crate::message::{CallMessage, GoodbyeMessage, HelloMessage};
+    use crate::types::{Body, Id};
+
+    const HELLO: KnownKind = KnownKind::Standard(StandardKind::Hello);
+    const GOODBYE: KnownKind = KnownKind::Standard(StandardKind::Goodbye);
+    const CALL: KnownKind = KnownKind::Standard(StandardKind::Call);
+
+    type Msg = StandardMessage<Map<Value>, Value>;
+
+    fn hello() -> Msg {
+        StandardMessage::Hello(HelloMessage::new(
+            Body::new(Value::from("peer")),
+            Meta::default(),
+        ))
+    }
+
+    fn goodbye() -> Msg {
+        StandardMessage::Goodbye(GoodbyeMessage::new(
+            uris::CLOSE_NORMAL_URI.clone(),
+            Meta::default(),
+        ))
+    }
+
+    fn call() -> Msg {
+        StandardMessage::Call(CallMessage::new(
+            Id::new(1),
+            Uri::from_static("test.echo").unwrap(),
+            Body::new(Value::Null),
+            Meta::default(),
+        ))
+    }
+
+    fn established() -> Session<Value> {
+        let mut session = Session::new();
+        session.send(HELLO).unwrap();
+        session.recv(&hello()).unwrap();
+        session
+    }
+
+    fn closing() -> Session<Value> {
+        let mut session = established();
+        session.send(GOODBYE).unwrap();
+        session
+    }
+
+    fn violation(kind: KnownKind, stage: Stage) -> ProtocolViolation {
+        ProtocolViolation { kind, stage }
+    }
+
+    #[test]
+    fn test_session_handshake() {
+        let mut session = Session::<Value>::new();
+        session.recv(&hello()).unwrap();
+        assert_eq!(session.stage(), Stage::Handshake);
+        assert_eq!(session.peer().unwrap().body(), &Value::from("peer"));
+        session.send(HELLO).unwrap();
+        assert!(session.is_established());
+
+        // Either peer may say hello first.
+        let mut session = Session::<Value>::new();
+        session.send(HELLO).unwrap();
+        assert_eq!(session.stage(), Stage::Handshake);
+        assert!(session.peer().is_none());
+        session.recv(&hello()).unwrap();
+        assert!(session.is_established());
+    }
+
+    #[test]
+    fn test_session_goodbye_during_handshake() {
+        let mut sent = Session::<Value>::new();
+        let mut received = Session::<Value>::new();
+        // The spec decides whether a peer may leave before saying hello.
+        if GOODBYE.stages().contains(&"handshake") {
+            sent.send(GOODBYE).unwrap();
+            received.recv(&goodbye()).unwrap();
+            assert_eq!(sent.stage(), Stage::Closed);
+            assert_eq!(received.stage(), Stage::Closed);
+        } else {
+            let err = violation(GOODBYE, Stage::Handshake);
+            assert_eq!(sent.send(GOODBYE), Err(err.clone()));
+            assert_eq!(received.recv(&goodbye()), Err(err.into()));
+            assert_eq!(sent.stage(), Stage::Handshake);
+            assert_eq!(received.stage(), Stage::Handshake);
+        }
+    }
+
+    #[test]
+    fn test_session_close() {
+        let mut session = closing();
+        assert_eq!(session.stage(), Stage::Closing);
+        session.recv(&goodbye()).unwrap();
+        assert_eq!(session.stage(), Stage::Closed);
+
+        // The peer may say goodbye first.
+        let mut session = established();
+        session.recv(&goodbye()).unwrap();
+        assert_eq!(session.stage(), Stage::Closing);
+        session.send(GOODBYE).unwrap();
+        assert_eq!(session.stage(), Stage::Closed);
+    }
+
+    #[test]
+    fn test_session_rejects_messages_outside_stages() {
+        let mut sessions = vec![(Session::new(), CALL, call())];
+        sessions.push((established(), HELLO, hello()));
+        sessions.push((closing(), CALL, call()));
+        sessions.push((closing(), HELLO, hello()));
+        for (mut session, kind, message) in sessions {
+            let stage = session.stage();
+            let err = violation(kind, stage);
+            assert_eq!(session.send(kind), Err(err.clone()));
+            assert_eq!(session.recv(&message), Err(err.into()));
+            assert_eq!(session.stage(), stage);
+        }
+    }
+
+    #[test]
+    fn test_session_abort() {
+        for session in [Session::new(), established(), closing()].iter_mut() {
+            session.abort();
+            assert_eq!(session.stage(), Stage::Closed);
+            for (kind, message) in [(HELLO, hello()), (GOODBYE, goodbye()), (CALL, call())].iter() {
+                let err = violation(*kind, Stage::Closed);
+                assert_eq!(session.send(*kind), Err(err.clone()));
+                assert_eq!(session.recv(message), Err(err.into()));
+            }
+        }
+    }
+}
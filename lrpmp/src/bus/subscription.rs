@@ -0,0 +1,98 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::Stream;
+
+use super::client::Client;
+use super::{Error, Meta, Uri};
+use crate::types::MatchPolicy;
+
+/// An event received from a subscription.
+#[derive(Debug, Clone)]
+pub struct Event<V> {
+    topic: Uri,
+    body: V,
+    meta: Meta<V>,
+}
+
+impl<V> Event<V> {
+    pub fn new(topic: Uri, body: V, meta: Meta<V>) -> Self {
+        Self { topic, body, meta }
+    }
+
+    /// Returns the topic the event was published to.
+    pub fn topic(&self) -> &Uri {
+        &self.topic
+    }
+
+    pub fn body(&self) -> &V {
+        &self.body
+    }
+
+    pub fn meta(&self) -> &Meta<V> {
+        &self.meta
+    }
+
+    pub fn into_parts(self) -> (Uri, V, Meta<V>) {
+        (self.topic, self.body, self.meta)
+    }
+}
+
+/// A stream of events for a topic, created with [`Client::subscribe`].
+///
+/// Dropping a subscription stops events being yielded, but only
+/// [`Subscription::unsubscribe`] tells the remote peer to stop sending
+/// them.
+pub struct Subscription<V> {
+    id: u64,
+    topic: Uri,
+    policy: MatchPolicy,
+    client: Client<V>,
+    events: UnboundedReceiver<Event<V>>,
+}
+
+impl<V> Subscription<V> {
+    pub(super) fn new(
+        client: Client<V>,
+        id: u64,
+        topic: Uri,
+        policy: MatchPolicy,
+        events: UnboundedReceiver<Event<V>>,
+    ) -> Self {
+        Self {
+            id,
+            topic,
+            policy,
+            client,
+            events,
+        }
+    }
+
+    /// Returns the subscribed topic.
+    pub fn topic(&self) -> &Uri {
+        &self.topic
+    }
+
+    /// Returns the policy the topic is matched under.
+    pub fn policy(&self) -> MatchPolicy {
+        self.policy
+    }
+
+    /// Unsubscribes from the topic.
+    pub fn unsubscribe(self) -> impl Future<Output = Result<(), Error<V>>>
+    where
+        V: From<String>,
+    {
+        self.client.unsubscribe(self.id, self.topic, self.policy)
+    }
+}
+
+impl<V> Stream for Subscription<V> {
+    type Item = Event<V>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
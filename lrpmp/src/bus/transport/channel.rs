@@ -0,0 +1,53 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::{self, SendError, UnboundedReceiver, UnboundedSender};
+use futures::{Sink, Stream};
+
+use crate::bus::BusMessage;
+
+/// Returns a pair of connected in-memory transports.
+///
+/// Messages written to one transport are read from the other.
+pub fn channel<V>() -> (ChannelTransport<V>, ChannelTransport<V>) {
+    let (a_tx, a_rx) = mpsc::unbounded();
+    let (b_tx, b_rx) = mpsc::unbounded();
+    let a = ChannelTransport { tx: a_tx, rx: b_rx };
+    let b = ChannelTransport { tx: b_tx, rx: a_rx };
+    (a, b)
+}
+
+/// An in-memory transport created with [`channel`].
+#[derive(Debug)]
+pub struct ChannelTransport<V> {
+    tx: UnboundedSender<BusMessage<V>>,
+    rx: UnboundedReceiver<BusMessage<V>>,
+}
+
+impl<V> Stream for ChannelTransport<V> {
+    type Item = BusMessage<V>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+impl<V> Sink<BusMessage<V>> for ChannelTransport<V> {
+    type Error = SendError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.tx).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: BusMessage<V>) -> Result<(), Self::Error> {
+        Pin::new(&mut self.tx).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.tx).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.tx).poll_close(cx)
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::buf::{BufExt, BufMutExt};
+use bytes::{Bytes, BytesMut};
+use futures::{ready, Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use crate::bus::BusMessage;
+use crate::codec::{cbor, json};
+use crate::message::Message;
+
+/// The serialization used for each frame of a [`FramedTransport`].
+pub trait Format {
+    /// The value type of messages in this format.
+    type Value;
+    /// The error produced when encoding or decoding a message.
+    type Error;
+
+    /// Encodes a message into a single frame.
+    fn encode(&self, message: &BusMessage<Self::Value>) -> Result<Bytes, Self::Error>;
+
+    /// Decodes a message from a single frame.
+    fn decode(&self, frame: Bytes) -> Result<BusMessage<Self::Value>, Self::Error>;
+}
+
+/// Frames encoded with [`codec::json`](crate::codec::json).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Format for Json {
+    type Value = json::Value;
+    type Error = json::Error;
+
+    fn encode(&self, message: &BusMessage<Self::Value>) -> Result<Bytes, Self::Error> {
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = json::MessageEncoder::from_writer(&mut writer);
+        message.encode_ref(&mut encoder)?;
+        Ok(writer.into_inner().freeze())
+    }
+
+    fn decode(&self, frame: Bytes) -> Result<BusMessage<Self::Value>, Self::Error> {
+        let mut decoder = json::MessageDecoder::from_reader(frame.reader());
+        BusMessage::decode(&mut decoder)
+    }
+}
+
+/// Frames encoded with [`codec::cbor`](crate::codec::cbor).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+impl Format for Cbor {
+    type Value = cbor::Value;
+    type Error = cbor::Error;
+
+    fn encode(&self, message: &BusMessage<Self::Value>) -> Result<Bytes, Self::Error> {
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = cbor::MessageEncoder::from_writer(&mut writer);
+        message.encode_ref(&mut encoder)?;
+        Ok(writer.into_inner().freeze())
+    }
+
+    fn decode(&self, frame: Bytes) -> Result<BusMessage<Self::Value>, Self::Error> {
+        let mut decoder = cbor::MessageDecoder::from_reader(frame.reader());
+        BusMessage::decode(&mut decoder)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Error produced when writing to a [`FramedTransport`].
+#[derive(Debug)]
+pub enum FramedError<E> {
+    /// The underlying byte stream failed.
+    Io(io::Error),
+    /// The message could not be encoded.
+    Format(E),
+}
+
+impl<E> From<io::Error> for FramedError<E> {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for FramedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {}", err),
+            Self::Format(err) => write!(f, "format error: {:?}", err),
+        }
+    }
+}
+
+/// A transport over a byte stream.
+///
+/// Each message is encoded with the given [`Format`] and written as a
+/// frame prefixed with its length as a big-endian `u32`.
+///
+/// The stream of messages ends when the byte stream is closed, fails, or
+/// a frame is not a valid message; a peer sending malformed messages is
+/// treated as a protocol violation.
+pub struct FramedTransport<T, F> {
+    inner: Framed<T, LengthDelimitedCodec>,
+    format: F,
+    terminated: bool,
+}
+
+/// A [`FramedTransport`] over a TCP connection.
+pub type TcpTransport<F> = FramedTransport<TcpStream, F>;
+
+/// A [`FramedTransport`] over a Unix domain socket.
+#[cfg(unix)]
+pub type UnixTransport<F> = FramedTransport<UnixStream, F>;
+
+impl<T, F> FramedTransport<T, F>
+where
+    T: AsyncRead + AsyncWrite,
+{
+    /// Constructs a transport over any byte stream.
+    ///
+    /// For an in-memory transport, pass one half of a connected pair
+    /// such as [`UnixStream::pair`](tokio::net::UnixStream::pair).
+    pub fn new(io: T, format: F) -> Self {
+        Self {
+            inner: Framed::new(io, LengthDelimitedCodec::new()),
+            format,
+            terminated: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<F> TcpTransport<F> {
+    /// Opens a TCP connection to the address.
+    pub async fn connect<A: ToSocketAddrs>(addr: A, format: F) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(Self::new(stream, format))
+    }
+}
+
+#[cfg(unix)]
+impl<F> UnixTransport<F> {
+    /// Opens a connection to the Unix domain socket at the path.
+    pub async fn connect<P: AsRef<Path>>(path: P, format: F) -> io::Result<Self> {
+        let stream = UnixStream::connect(path).await?;
+        Ok(Self::new(stream, format))
+    }
+}
+
+impl<T, F> Stream for FramedTransport<T, F>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: Format + Unpin,
+{
+    type Item = BusMessage<F::Value>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if self.terminated {
+            return Poll::Ready(None);
+        }
+        let message = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            Some(Ok(frame)) => self.format.decode(frame.freeze()).ok(),
+            Some(Err(_)) | None => None,
+        };
+        self.terminated = message.is_none();
+        Poll::Ready(message)
+    }
+}
+
+impl<T, F> Sink<BusMessage<F::Value>> for FramedTransport<T, F>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: Format + Unpin,
+{
+    type Error = FramedError<F::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: BusMessage<F::Value>) -> Result<(), Self::Error> {
+        let frame = self.format.encode(&item).map_err(FramedError::Format)?;
+        Pin::new(&mut self.inner).start_send(frame)?;
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(cx).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::bus::{channel, Client, Hub, MetaValue, RpcClient, SessionDetails};
+    use crate::types::{Meta, Uri};
+
+    fn details<V: From<String>>() -> SessionDetails<V> {
+        SessionDetails::new(V::from("test".to_string()), Meta::default())
+    }
+
+    async fn call_over<T, F>(client_io: T, server_io: T, format: F)
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        F: Format + Clone + Unpin,
+        F::Value: MetaValue + PartialEq + fmt::Debug + Send + 'static,
+    {
+        let hub = Hub::<F::Value>::new();
+        let (callee_transport, callee_remote) = channel();
+        let (callee, callee_connection) = Client::new(callee_transport, details());
+        let (mut caller, caller_connection) =
+            Client::new(FramedTransport::new(client_io, format.clone()), details());
+        let procedure = Uri::from_static("test.echo").unwrap();
+
+        let peers = async move {
+            callee
+                .register(procedure.clone(), |_, body, meta| {
+                    future::ok((body.into_inner(), meta))
+                })
+                .await
+                .unwrap();
+            let body = F::Value::from("hello".to_string());
+            let (reply, _) = caller.call(&procedure, body.clone()).await.unwrap();
+            assert_eq!(reply, body);
+        };
+
+        future::join(
+            future::join(
+                hub.serve(callee_remote),
+                hub.serve(FramedTransport::new(server_io, format)),
+            ),
+            future::join3(callee_connection, caller_connection, peers),
+        )
+        .await;
+    }
+
+    async fn tcp_pair() -> (TcpStream, TcpStream) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (client, server) = future::join(TcpStream::connect(addr), listener.accept()).await;
+        (client.unwrap(), server.unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn test_tcp_transport_json() {
+        let (client, server) = tcp_pair().await;
+        call_over(client, server, Json).await;
+    }
+
+    #[tokio::test]
+    async fn test_tcp_transport_cbor() {
+        let (client, server) = tcp_pair().await;
+        call_over(client, server, Cbor).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_transport_json() {
+        let (client, server) = UnixStream::pair().unwrap();
+        call_over(client, server, Json).await;
+    }
+}
@@ -0,0 +1,27 @@
+use futures::{Sink, Stream};
+
+use super::message::BusMessage;
+
+mod channel;
+#[cfg(feature = "tokio-transport")]
+mod framed;
+#[cfg(feature = "websocket-transport")]
+mod websocket;
+
+pub use self::channel::*;
+#[cfg(feature = "tokio-transport")]
+pub use self::framed::*;
+#[cfg(feature = "websocket-transport")]
+pub use self::websocket::*;
+
+pub trait Read<V>: Stream<Item = BusMessage<V>> {}
+
+pub trait Write<V>: Sink<BusMessage<V>> {}
+
+pub trait Transport<V>: Read<V> + Write<V> {}
+
+impl<T, V> Read<V> for T where T: Stream<Item = BusMessage<V>> {}
+
+impl<T, V> Write<V> for T where T: Sink<BusMessage<V>> {}
+
+impl<T, V> Transport<V> for T where T: Read<V> + Write<V> {}
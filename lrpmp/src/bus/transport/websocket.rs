@@ -0,0 +1,350 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+use tokio_tungstenite::WebSocketStream;
+
+use super::framed::{Cbor, Format, Json};
+use crate::bus::BusMessage;
+
+/// The WebSocket subprotocol for messages encoded with `codec::json`.
+pub const JSON_SUBPROTOCOL: &str = "lrpmp.json";
+
+/// The WebSocket subprotocol for messages encoded with `codec::cbor`.
+pub const CBOR_SUBPROTOCOL: &str = "lrpmp.cbor";
+
+/// A [`Format`] that can be negotiated as a WebSocket subprotocol.
+pub trait WebSocketFormat: Format {
+    /// The subprotocol name.
+    const SUBPROTOCOL: &'static str;
+
+    /// Wraps an encoded message in a WebSocket frame.
+    fn into_frame(encoded: Bytes) -> WsMessage;
+
+    /// Unwraps an encoded message from a WebSocket frame, returning `None`
+    /// if the frame is not of the type used by this format.
+    fn from_frame(frame: WsMessage) -> Option<Bytes>;
+}
+
+impl WebSocketFormat for Json {
+    const SUBPROTOCOL: &'static str = JSON_SUBPROTOCOL;
+
+    fn into_frame(encoded: Bytes) -> WsMessage {
+        let text = String::from_utf8(encoded.to_vec()).expect("json is valid utf-8");
+        WsMessage::Text(text)
+    }
+
+    fn from_frame(frame: WsMessage) -> Option<Bytes> {
+        match frame {
+            WsMessage::Text(text) => Some(text.into()),
+            _ => None,
+        }
+    }
+}
+
+impl WebSocketFormat for Cbor {
+    const SUBPROTOCOL: &'static str = CBOR_SUBPROTOCOL;
+
+    fn into_frame(encoded: Bytes) -> WsMessage {
+        WsMessage::Binary(encoded.to_vec())
+    }
+
+    fn from_frame(frame: WsMessage) -> Option<Bytes> {
+        match frame {
+            WsMessage::Binary(data) => Some(data.into()),
+            _ => None,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Error produced when connecting or writing to a [`WebSocketTransport`].
+#[derive(Debug)]
+pub enum WebSocketError<E> {
+    /// The WebSocket connection failed.
+    WebSocket(WsError),
+    /// The peer did not agree on a supported subprotocol.
+    Subprotocol,
+    /// The message could not be encoded.
+    Format(E),
+}
+
+impl<E> From<WsError> for WebSocketError<E> {
+    fn from(err: WsError) -> Self {
+        Self::WebSocket(err)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for WebSocketError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WebSocket(err) => write!(f, "websocket error: {}", err),
+            Self::Subprotocol => write!(f, "no supported subprotocol"),
+            Self::Format(err) => write!(f, "format error: {:?}", err),
+        }
+    }
+}
+
+/// A transport over a WebSocket connection.
+///
+/// JSON messages are sent as text frames and CBOR messages as binary
+/// frames. Pings are answered automatically. The stream of messages ends
+/// when the connection is closed, fails, or the peer sends a frame that
+/// is not a valid message in the negotiated format.
+pub struct WebSocketTransport<S, F> {
+    inner: WebSocketStream<S>,
+    format: F,
+    terminated: bool,
+}
+
+impl<S, F> WebSocketTransport<S, F>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: WebSocketFormat,
+{
+    /// Performs the client handshake over a stream, requesting the
+    /// subprotocol of the given format.
+    pub async fn connect<R>(
+        request: R,
+        stream: S,
+        format: F,
+    ) -> Result<Self, WebSocketError<F::Error>>
+    where
+        R: IntoClientRequest + Unpin,
+    {
+        let mut request = request.into_client_request()?;
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(F::SUBPROTOCOL),
+        );
+        let (inner, response) = tokio_tungstenite::client_async(request, stream).await?;
+        let subprotocol = response.headers().get(SEC_WEBSOCKET_PROTOCOL);
+        if subprotocol.map(HeaderValue::as_bytes) != Some(F::SUBPROTOCOL.as_bytes()) {
+            return Err(WebSocketError::Subprotocol);
+        }
+        Ok(Self::from_stream(inner, format))
+    }
+
+    /// Constructs a transport from an established WebSocket connection
+    /// already using the format's subprotocol.
+    pub fn from_stream(inner: WebSocketStream<S>, format: F) -> Self {
+        Self {
+            inner,
+            format,
+            terminated: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &WebSocketStream<S> {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> WebSocketStream<S> {
+        self.inner
+    }
+}
+
+impl<S, F> Stream for WebSocketTransport<S, F>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: WebSocketFormat + Unpin,
+{
+    type Item = BusMessage<F::Value>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        while !self.terminated {
+            let frame = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                // Pongs for pings are queued by the connection itself.
+                Some(Ok(WsMessage::Ping(_))) | Some(Ok(WsMessage::Pong(_))) => continue,
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => None,
+                Some(Ok(frame)) => F::from_frame(frame),
+            };
+            let message = frame.and_then(|frame| self.format.decode(frame).ok());
+            self.terminated = message.is_none();
+            if message.is_some() {
+                return Poll::Ready(message);
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+impl<S, F> Sink<BusMessage<F::Value>> for WebSocketTransport<S, F>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: WebSocketFormat + Unpin,
+{
+    type Error = WebSocketError<F::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: BusMessage<F::Value>) -> Result<(), Self::Error> {
+        let encoded = self.format.encode(&item).map_err(WebSocketError::Format)?;
+        Pin::new(&mut self.inner).start_send(F::into_frame(encoded))?;
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(cx).map_err(Into::into)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A WebSocket transport accepted with the subprotocol chosen by the
+/// client.
+pub enum Negotiated<S> {
+    Json(WebSocketTransport<S, Json>),
+    Cbor(WebSocketTransport<S, Cbor>),
+}
+
+impl<S> Negotiated<S> {
+    /// Returns the negotiated subprotocol.
+    pub fn subprotocol(&self) -> &'static str {
+        match self {
+            Self::Json(_) => JSON_SUBPROTOCOL,
+            Self::Cbor(_) => CBOR_SUBPROTOCOL,
+        }
+    }
+}
+
+/// Performs the server handshake over a stream.
+///
+/// The first subprotocol offered by the client that is supported is
+/// selected. Clients offering none are rejected.
+pub async fn accept<S>(stream: S) -> Result<Negotiated<S>, WsError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let selected = Arc::new(Mutex::new(None));
+    // The error response type is dictated by the handshake callback.
+    #[allow(clippy::result_large_err)]
+    let callback = {
+        let selected = selected.clone();
+        move |request: &Request, mut response: Response| {
+            let subprotocol = select_subprotocol(request).ok_or_else(|| {
+                let mut response = ErrorResponse::new(Some("no supported subprotocol".into()));
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                response
+            })?;
+            response.headers_mut().insert(
+                SEC_WEBSOCKET_PROTOCOL,
+                HeaderValue::from_static(subprotocol),
+            );
+            *selected.lock().unwrap() = Some(subprotocol);
+            Ok(response)
+        }
+    };
+    let inner = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+    let subprotocol = selected.lock().unwrap().take();
+    match subprotocol {
+        Some(JSON_SUBPROTOCOL) => Ok(Negotiated::Json(WebSocketTransport::from_stream(
+            inner, Json,
+        ))),
+        Some(CBOR_SUBPROTOCOL) => Ok(Negotiated::Cbor(WebSocketTransport::from_stream(
+            inner, Cbor,
+        ))),
+        _ => unreachable!(),
+    }
+}
+
+fn select_subprotocol(request: &Request) -> Option<&'static str> {
+    request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .find_map(|offered| match offered {
+            JSON_SUBPROTOCOL => Some(JSON_SUBPROTOCOL),
+            CBOR_SUBPROTOCOL => Some(CBOR_SUBPROTOCOL),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::bus::{Client, Hub, MetaValue, RpcClient, SessionDetails};
+    use crate::codec::{cbor, json};
+    use crate::types::{Meta, Uri};
+
+    fn details<V: From<String>>() -> SessionDetails<V> {
+        SessionDetails::new(V::from("test".to_string()), Meta::default())
+    }
+
+    async fn call_over<F>(format: F, expected: &'static str)
+    where
+        F: WebSocketFormat + Unpin,
+        F::Error: fmt::Debug,
+        F::Value: MetaValue + PartialEq + fmt::Debug + Send + 'static,
+    {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let json_hub = Hub::<json::Value>::new();
+        let cbor_hub = Hub::<cbor::Value>::new();
+        let procedure = Uri::from_static("test.echo").unwrap();
+
+        let server = async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let negotiated = accept(stream).await.unwrap();
+            assert_eq!(negotiated.subprotocol(), expected);
+            match negotiated {
+                Negotiated::Json(transport) => json_hub.serve(transport).await,
+                Negotiated::Cbor(transport) => cbor_hub.serve(transport).await,
+            }
+        };
+
+        let client = async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let transport = WebSocketTransport::connect("ws://localhost/", stream, format)
+                .await
+                .unwrap();
+            let (mut client, connection) = Client::new(transport, details());
+            let peer = async move {
+                let body = F::Value::from("hello".to_string());
+                // Nothing is registered, so the call is rejected by the hub.
+                let err = client.call(&procedure, body).await.unwrap_err();
+                match err {
+                    crate::bus::Error::Remote(err) => {
+                        assert_eq!(err.uri(), &crate::uris::ERROR_NO_SUCH_PROCEDURE_URI)
+                    }
+                    other => panic!("unexpected error {:?}", other),
+                }
+            };
+            future::join(connection, peer).await;
+        };
+
+        future::join(server, client).await;
+    }
+
+    #[tokio::test]
+    async fn test_websocket_transport_json() {
+        call_over(Json, JSON_SUBPROTOCOL).await;
+    }
+
+    #[tokio::test]
+    async fn test_websocket_transport_cbor() {
+        call_over(Cbor, CBOR_SUBPROTOCOL).await;
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use semver::Version;
+
+use crate::codec::generic::{Map, Meta};
+use crate::types::{ConcreteBasicValue, IntoBasicValue};
+
+/// The meta key of the comma separated spec versions a peer supports.
+pub const VERSIONS_KEY: &str = "versions";
+/// The meta key of the comma separated features a peer supports.
+pub const FEATURES_KEY: &str = "features";
+
+static SPEC_VERSION: Lazy<Version> =
+    Lazy::new(|| Version::parse(crate::SPEC_VERSION).expect("generated from a parsed version"));
+
+/// A meta value that may hold a string, as the values of every codec do.
+pub trait MetaValue: Clone + From<String> {
+    fn to_meta_str(&self) -> Option<String>;
+}
+
+impl<V> MetaValue for V
+where
+    V: Clone + From<String>,
+    V: IntoBasicValue<ConcreteBasicValue<Map<V>, V>, Map<V>, V>,
+{
+    fn to_meta_str(&self) -> Option<String> {
+        match self.clone().into_basic() {
+            Ok(ConcreteBasicValue::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The spec versions and features a peer supports, carried in the meta of
+/// the `HELLO` it sends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    versions: Vec<Version>,
+    features: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn new(mut versions: Vec<Version>, features: Vec<String>) -> Self {
+        versions.sort_by(|a, b| b.cmp(a));
+        versions.dedup();
+        Self { versions, features }
+    }
+
+    /// Supports only the spec version this crate was compiled against.
+    pub fn current() -> Self {
+        Self::new(vec![SPEC_VERSION.clone()], Vec::new())
+    }
+
+    pub fn with_feature<S: Into<String>>(mut self, feature: S) -> Self {
+        self.features.push(feature.into());
+        self
+    }
+
+    /// Returns the supported versions, highest first.
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    /// Reads the capabilities from a peer's meta.
+    ///
+    /// Returns `None` if the peer doesn't advertise any versions. Versions
+    /// that fail to parse are ignored.
+    pub fn from_meta<V: MetaValue>(meta: &Meta<V>) -> Option<Self> {
+        let meta = meta.as_inner();
+        let versions = meta.get(VERSIONS_KEY)?.to_meta_str()?;
+        let versions = split_list(&versions)
+            .filter_map(|v| Version::parse(v).ok())
+            .collect();
+        let features = meta
+            .get(FEATURES_KEY)
+            .and_then(MetaValue::to_meta_str)
+            .map(|features| split_list(&features).map(String::from).collect())
+            .unwrap_or_default();
+        Some(Self::new(versions, features))
+    }
+
+    /// Writes the capabilities to the meta to say hello with.
+    pub fn write_meta<V: From<String>>(&self, meta: &mut Meta<V>) {
+        let versions: Vec<_> = self.versions.iter().map(ToString::to_string).collect();
+        let meta = meta.as_inner_mut();
+        meta.insert(VERSIONS_KEY.into(), V::from(versions.join(",")));
+        meta.insert(FEATURES_KEY.into(), V::from(self.features.join(",")));
+    }
+
+    /// Picks the highest version both peers support, along with the
+    /// features both support.
+    ///
+    /// Versions are compatible as by a caret requirement, so `0.2.0` and
+    /// `0.2.1` are, and the lower of the two is agreed on.
+    pub fn negotiate(&self, peer: &Capabilities) -> Result<NegotiatedVersion, UnsupportedVersion> {
+        let version = self
+            .versions
+            .iter()
+            .find_map(|v| {
+                let p = peer.versions.iter().find(|p| is_compatible(v, p))?;
+                Some(v.min(p))
+            })
+            .ok_or_else(|| UnsupportedVersion {
+                supported: self.versions.clone(),
+                peer: peer.versions.clone(),
+            })?;
+        let features = self
+            .features
+            .iter()
+            .filter(|f| peer.features.contains(f))
+            .cloned()
+            .collect();
+        Ok(NegotiatedVersion {
+            version: version.clone(),
+            features,
+        })
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+/// The version and features agreed on by both peers of a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegotiatedVersion {
+    pub version: Version,
+    pub features: Vec<String>,
+}
+
+impl NegotiatedVersion {
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Returns the capabilities to reply to the peer with, which name only
+    /// what was agreed on.
+    pub fn to_capabilities(&self) -> Capabilities {
+        Capabilities::new(vec![self.version.clone()], self.features.clone())
+    }
+}
+
+/// Error produced when peers share no spec version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedVersion {
+    pub supported: Vec<Version>,
+    pub peer: Vec<Version>,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "no supported version (supported [{}], peer supports [{}])",
+            join_versions(&self.supported),
+            join_versions(&self.peer)
+        )
+    }
+}
+
+/// Returns `true` if the versions differ only where semver allows without
+/// breaking changes.
+fn is_compatible(a: &Version, b: &Version) -> bool {
+    match (a.major, b.major) {
+        (0, 0) if a.minor == 0 && b.minor == 0 => a.patch == b.patch,
+        (0, 0) => a.minor == b.minor,
+        (a_major, b_major) => a_major == b_major,
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn join_versions(versions: &[Version]) -> String {
+    let versions: Vec<_> = versions.iter().map(ToString::to_string).collect();
+    versions.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::json::Value;
+
+    fn version(v: &str) -> Version {
+        Version::parse(v).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate_highest_mutual_version() {
+        let local = Capabilities::new(vec![version("0.1.0"), version("0.2.0")], Vec::new())
+            .with_feature("streaming")
+            .with_feature("compression");
+        let peer = Capabilities::new(
+            vec![version("0.1.0"), version("0.2.0"), version("0.3.0")],
+            vec!["compression".into()],
+        );
+        let mut meta = Meta::<Value>::default();
+        peer.write_meta(&mut meta);
+        let peer = Capabilities::from_meta(&meta).unwrap();
+        let negotiated = local.negotiate(&peer).unwrap();
+        assert_eq!(negotiated.version, version("0.2.0"));
+        assert_eq!(negotiated.features, vec!["compression".to_string()]);
+
+        let old_peer = Capabilities::new(vec![version("0.0.1")], Vec::new());
+        assert!(local.negotiate(&old_peer).is_err());
+
+        // Patch versions that differ still negotiate, on the lower one.
+        let patched_peer = Capabilities::new(vec![version("0.2.3")], Vec::new());
+        let negotiated = local.negotiate(&patched_peer).unwrap();
+        assert_eq!(negotiated.version, version("0.2.0"));
+        let newer_peer = Capabilities::new(vec![version("0.3.0")], Vec::new());
+        assert!(local.negotiate(&newer_peer).is_err());
+    }
+}
@@ -1,15 +1,28 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
 
-use serde::{Deserialize, Serialize};
+use bytes::Bytes;
+use bytestring::ByteString;
+use serde::de::{DeserializeOwned, Error as _, IgnoredAny};
+use serde::{ser, Deserialize, Serialize};
 
 use serde_cbor::de::{Deserializer, IoRead};
 use serde_cbor::ser::{IoWrite, Serializer};
 use serde_cbor::Error as InnerError;
 
+#[cfg(feature = "tokio-codec")]
+use {
+    super::stream,
+    bytes::{buf::BufMutExt, BytesMut},
+};
+
+use super::{generic, raw};
 use crate::io::{Read, Write};
 use crate::message::{self as msg, Message, MessageError};
 use crate::serde::{ArrayDecoder, ArrayEncoder, ArrayFieldDecoder, ArrayFieldEncoder};
-use crate::types::{BasicValue, ConcreteBasicValue, FromBasicValuePart, IntoBasicValue, KnownKind};
+use crate::types::{
+    BasicType, BasicValue, ConcreteBasicValue, FromBasicValuePart, IntoBasicValue, KnownKind,
+};
 
 pub use serde_cbor::Value;
 
@@ -123,6 +136,273 @@ fn all_keys_are_string(map: &BTreeMap<Value, Value>) -> bool {
     })
 }
 
+/// A `tokio_util` codec for messages of type `T`, encoded as CBOR arrays
+/// with no additional framing.
+#[cfg(feature = "tokio-codec")]
+pub type MessageCodec<T> = stream::MessageCodec<RawFormat, T>;
+
+/// The state of a scan for the end of a CBOR array.
+#[cfg(feature = "tokio-codec")]
+#[derive(Debug, Default)]
+pub struct Scanner {
+    offset: usize,
+    /// The items left in each array or map being scanned, or `None` if of
+    /// indefinite length.
+    pending: Vec<Option<u64>>,
+}
+
+#[cfg(feature = "tokio-codec")]
+impl stream::Format for RawFormat {
+    type Map = Map;
+    type Val = Val;
+    type Error = InnerError;
+    type Scanner = Scanner;
+
+    fn scan(s: &mut Scanner, src: &mut BytesMut) -> Result<Option<BytesMut>, InnerError> {
+        loop {
+            let item = &src[s.offset..];
+            let initial = match item.first() {
+                Some(initial) => *initial,
+                None => return Ok(None),
+            };
+            let head_len = match initial & 0x1f {
+                24 => 2,
+                25 => 3,
+                26 => 5,
+                27 => 9,
+                _ => 1,
+            };
+            if item.len() < head_len {
+                return Ok(None);
+            }
+            let (major, arg, _) = read_head(item)?;
+            if s.pending.is_empty() && major != MAJOR_ARRAY {
+                return Err(InnerError::custom("expected array"));
+            }
+            let mut len = head_len;
+            let complete = match (major, arg) {
+                (MAJOR_BYTES, Some(n)) | (MAJOR_TEXT, Some(n)) => {
+                    if ((item.len() - head_len) as u64) < n {
+                        return Ok(None);
+                    }
+                    len += n as usize;
+                    true
+                }
+                (MAJOR_ARRAY, Some(0)) | (MAJOR_MAP, Some(0)) => true,
+                (MAJOR_ARRAY, Some(n)) => {
+                    s.pending.push(Some(n));
+                    false
+                }
+                (MAJOR_MAP, Some(n)) => {
+                    let n = n
+                        .checked_mul(2)
+                        .ok_or_else(|| InnerError::custom("map too long"))?;
+                    s.pending.push(Some(n));
+                    false
+                }
+                // Indefinite length strings are chunks up to a break.
+                (MAJOR_BYTES, None)
+                | (MAJOR_TEXT, None)
+                | (MAJOR_ARRAY, None)
+                | (MAJOR_MAP, None) => {
+                    s.pending.push(None);
+                    false
+                }
+                (MAJOR_SIMPLE, None) => match s.pending.pop() {
+                    Some(None) => true,
+                    _ => return Err(InnerError::custom("unexpected break")),
+                },
+                // A tag applies to the item following it.
+                (MAJOR_TAG, Some(_)) => false,
+                (_, Some(_)) => true,
+                (_, None) => return Err(InnerError::custom("invalid additional info")),
+            };
+            s.offset += len;
+            if !complete {
+                continue;
+            }
+            loop {
+                match s.pending.last_mut() {
+                    None => {
+                        let len = s.offset;
+                        *s = Scanner::default();
+                        return Ok(Some(src.split_to(len)));
+                    }
+                    Some(Some(n)) => {
+                        *n -= 1;
+                        if *n > 0 {
+                            break;
+                        }
+                        s.pending.pop();
+                    }
+                    Some(None) => break,
+                }
+            }
+        }
+    }
+
+    fn decode<T>(frame: &[u8]) -> Result<T, Error>
+    where
+        T: Message<Map, Val>,
+    {
+        let mut deserializer = Deserializer::from_slice(frame);
+        T::decode(ArrayDecoder::new(&mut deserializer))
+    }
+
+    fn encode<T>(item: T, dst: &mut BytesMut) -> Result<(), Error>
+    where
+        T: Message<Map, Val>,
+    {
+        let mut serializer = Serializer::new(IoWrite::new(dst.writer()));
+        item.encode(ArrayEncoder::new(&mut serializer))
+    }
+}
+
+/// The CBOR encoding for [`raw`] decoding and stream codecs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawFormat;
+
+/// A CBOR map or value, decoded on demand.
+pub type Raw = raw::Raw<RawFormat>;
+
+/// Decodes a CBOR message sharing its buffer.
+pub type BytesDecoder = raw::BytesDecoder<RawFormat>;
+
+const MAJOR_UNSIGNED: u8 = 0;
+#[cfg(feature = "tokio-codec")]
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+#[cfg(feature = "tokio-codec")]
+const MAJOR_TAG: u8 = 6;
+#[cfg(feature = "tokio-codec")]
+const MAJOR_SIMPLE: u8 = 7;
+const BREAK: u8 = 0xff;
+
+/// Reads the head of a data item, returning the major type, the argument
+/// (`None` if indefinite) and the length of the head.
+fn read_head(item: &[u8]) -> Result<(u8, Option<u64>, usize), InnerError> {
+    let initial = *item
+        .first()
+        .ok_or_else(|| InnerError::custom("unexpected end of input"))?;
+    let size = match initial & 0x1f {
+        info @ 0..=23 => return Ok((initial >> 5, Some(u64::from(info)), 1)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Ok((initial >> 5, None, 1)),
+        _ => return Err(InnerError::custom("invalid additional info")),
+    };
+    let bytes = item
+        .get(1..=size)
+        .ok_or_else(|| InnerError::custom("unexpected end of input"))?;
+    let arg = bytes.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b));
+    Ok((initial >> 5, Some(arg), 1 + size))
+}
+
+/// Returns the encoded length of the data item at the start of `item`.
+fn item_len(item: &[u8]) -> Result<usize, InnerError> {
+    let mut deserializer = Deserializer::from_slice(item);
+    IgnoredAny::deserialize(&mut deserializer)?;
+    Ok(deserializer.byte_offset())
+}
+
+impl raw::Format for RawFormat {
+    type Error = InnerError;
+
+    fn split_array(src: &Bytes) -> Result<VecDeque<Bytes>, Self::Error> {
+        let (major, len, mut offset) = read_head(src)?;
+        if major != MAJOR_ARRAY {
+            return Err(InnerError::custom("expected array"));
+        }
+        let len = len
+            .map(usize::try_from)
+            .transpose()
+            .map_err(InnerError::custom)?;
+        // Each item takes at least a byte, so a longer array can't be
+        // valid and isn't allocated for.
+        if let Some(len) = len {
+            if len > src.len() - offset {
+                return Err(InnerError::custom("unexpected end of input"));
+            }
+        }
+        let mut items = VecDeque::with_capacity(len.unwrap_or(0));
+        loop {
+            match len {
+                Some(len) if items.len() == len => break,
+                None if src.get(offset) == Some(&BREAK) => {
+                    offset += 1;
+                    break;
+                }
+                _ => (),
+            }
+            let end = offset + item_len(&src[offset..])?;
+            items.push_back(src.slice(offset..end));
+            offset = end;
+        }
+        if offset != src.len() {
+            return Err(InnerError::custom("trailing data"));
+        }
+        Ok(items)
+    }
+
+    fn basic_type(item: &[u8]) -> BasicType {
+        match item.first().map(|initial| initial >> 5) {
+            Some(MAJOR_UNSIGNED) => BasicType::U64,
+            Some(MAJOR_TEXT) => BasicType::Str,
+            Some(MAJOR_MAP) => BasicType::Map,
+            _ => BasicType::Val,
+        }
+    }
+
+    fn decode_u64(item: &[u8]) -> Result<u64, Self::Error> {
+        serde_cbor::from_slice(item)
+    }
+
+    fn decode_str(item: Bytes) -> Result<ByteString, Self::Error> {
+        // Indefinite length strings are split into chunks and can't be shared.
+        if let (MAJOR_TEXT, Some(len), start) = read_head(&item)? {
+            if start as u64 + len == item.len() as u64 {
+                if let Some(s) = raw::share_str(item.slice(start..)) {
+                    return Ok(s);
+                }
+            }
+        }
+        serde_cbor::from_slice::<String>(&item).map(ByteString::from)
+    }
+
+    fn decode<T: DeserializeOwned>(item: &[u8]) -> Result<T, Self::Error> {
+        serde_cbor::from_slice(item)
+    }
+
+    fn serialize<S: ser::Serializer>(item: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        // serde has no way to pass encoded bytes through, so the value is
+        // materialized to be written.
+        let value: Value = serde_cbor::from_slice(item).map_err(ser::Error::custom)?;
+        value.serialize(serializer)
+    }
+}
+
+impl raw::FromRaw<RawFormat> for Value {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
+impl raw::FromRaw<RawFormat> for Map {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
+impl raw::FromRaw<RawFormat> for generic::Map<Val> {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
 impl<B> IntoBasicValue<B, Map, Val> for Value
 where
     B: BasicValue<Map, Val>,
@@ -131,26 +411,42 @@ where
     type Error = B::Error;
 
     fn into_basic(self) -> Result<B, Self::Error> {
-        match self {
-            Value::Integer(i) if i >= 0 && i <= u8::max_value() as i128 => {
-                B::from_basic_u8(i as u8)
-            }
-            Value::Integer(i) if i >= 0 && i <= u64::max_value() as i128 => {
-                B::from_basic_u64(i as u64)
-            }
-            Value::Text(t) => B::from_basic_str(t),
-            Value::Map(src_map) if all_keys_are_string(&src_map) => {
-                let iter = src_map.into_iter().map(|(k, v)| {
-                    if let Value::Text(k) = k {
-                        (k, v)
-                    } else {
-                        unreachable!()
-                    }
-                });
-                B::from_basic_map(iter.collect())
-            }
-            val => B::from_basic_val(val),
+        value_into_basic(self)
+    }
+}
+
+impl<B> IntoBasicValue<B, generic::Map<Val>, Val> for Value
+where
+    B: BasicValue<generic::Map<Val>, Val>,
+    B: FromBasicValuePart<generic::Map<Val>, Val>,
+{
+    type Error = B::Error;
+
+    fn into_basic(self) -> Result<B, Self::Error> {
+        value_into_basic(self)
+    }
+}
+
+fn value_into_basic<B, M>(value: Value) -> Result<B, B::Error>
+where
+    B: FromBasicValuePart<M, Val>,
+    M: From<Map>,
+{
+    match value {
+        Value::Integer(i) if i >= 0 && i <= u8::max_value() as i128 => B::from_basic_u8(i as u8),
+        Value::Integer(i) if i >= 0 && i <= u64::max_value() as i128 => B::from_basic_u64(i as u64),
+        Value::Text(t) => B::from_basic_str(t),
+        Value::Map(src_map) if all_keys_are_string(&src_map) => {
+            let iter = src_map.into_iter().map(|(k, v)| {
+                if let Value::Text(k) = k {
+                    (k, v)
+                } else {
+                    unreachable!()
+                }
+            });
+            B::from_basic_map(iter.collect::<Map>().into())
         }
+        val => B::from_basic_val(val),
     }
 }
 
@@ -160,8 +456,8 @@ mod tests {
     use bytes::BytesMut;
 
     use super::*;
-    use crate::message::{HelloMessage, Message, StandardMessage};
-    use crate::types::{Body, Meta};
+    use crate::message::{CallMessage, HelloMessage, Message, StandardMessage};
+    use crate::types::{Body, Id, Meta, Uri};
 
     #[test]
     fn test_message_encoder_decoder() {
@@ -185,4 +481,86 @@ mod tests {
             other => panic!("unexpected message {:?}", other),
         }
     }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_message_codec_partial() {
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = MessageCodec::<StandardMessage<Map, Val>>::new();
+        let mut buf = BytesMut::new();
+        for body in &["1", "2"] {
+            let message = HelloMessage::new(
+                Body::new(Value::Text(body.to_string())),
+                Meta::new(Map::default()),
+            );
+            let message = message.into_standard().unwrap();
+            codec.encode(message, &mut buf).unwrap();
+        }
+        let mut src = buf.split_to(3);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.unsplit(buf);
+        for _ in 0..2 {
+            match codec.decode(&mut src).unwrap() {
+                Some(StandardMessage::Hello(_)) => (),
+                other => panic!("unexpected message {:?}", other),
+            }
+        }
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(src.is_empty());
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_message_codec_bytewise() {
+        use tokio_util::codec::Decoder;
+
+        let mut codec = MessageCodec::<StandardMessage<Map, Val>>::new();
+        // A HELLO with an indefinite length text body and a tagged meta value.
+        let message = [
+            0x83, 0x02, 0x7f, 0x61, 0x61, 0x61, 0x62, 0xff, 0xa1, 0x61, 0x74, 0xc1, 0x1a, 0x00,
+            0x00, 0x00, 0x01,
+        ];
+        let mut src = BytesMut::new();
+        for (i, b) in message.iter().enumerate() {
+            src.extend_from_slice(&[*b]);
+            let decoded = codec.decode(&mut src).unwrap();
+            assert_eq!(decoded.is_some(), i == message.len() - 1);
+        }
+        assert!(src.is_empty());
+
+        let mut src = BytesMut::from(&[0x02][..]);
+        assert!(codec.decode(&mut src).is_err());
+    }
+
+    #[test]
+    fn test_bytes_decoder_shares_buffer() {
+        let src_message = CallMessage::<Map, Val>::new(
+            Id::new(1),
+            Uri::from_static("test.echo").unwrap(),
+            Body::new(Value::Bool(true)),
+            Meta::new(Map::default()),
+        );
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = MessageEncoder::from_writer(&mut writer);
+        src_message.encode(&mut encoder).unwrap();
+        let src = writer.into_inner().freeze();
+        let message = StandardMessage::<Raw, Raw>::decode(BytesDecoder::new(src.clone())).unwrap();
+        let call = match message {
+            StandardMessage::Call(call) => call,
+            other => panic!("unexpected message {:?}", other),
+        };
+        let uri = call.uri.as_str().as_bytes();
+        assert_eq!(uri, b"test.echo");
+        assert_eq!(uri.as_ptr(), src[4..].as_ptr());
+        let body: Value = call.body.as_inner().parse().unwrap();
+        assert_eq!(body, Value::Bool(true));
+    }
+
+    #[test]
+    fn test_bytes_decoder_rejects_oversized_array() {
+        let src = Bytes::from_static(&[0x9b, 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let result = StandardMessage::<Raw, Raw>::decode(BytesDecoder::new(src));
+        assert!(result.is_err());
+    }
 }
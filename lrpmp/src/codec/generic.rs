@@ -1,6 +1,8 @@
 use std::collections::{BTreeMap, HashMap};
 use std::ops::{Deref, DerefMut};
 
+use serde::{Deserialize, Serialize};
+
 use super::json;
 use crate::types;
 
@@ -8,7 +10,8 @@ pub type Meta<V> = types::Meta<Map<V>, V>;
 
 type MapInner<V> = BTreeMap<String, V>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Map<V> {
     inner: MapInner<V>,
 }
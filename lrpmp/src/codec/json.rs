@@ -1,13 +1,30 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use serde::de::DeserializeOwned;
+use serde::ser;
 use serde::{Deserialize, Serialize};
 
 use serde_json::de::{Deserializer, IoRead};
 use serde_json::ser::Serializer;
+use serde_json::value::RawValue;
 use serde_json::Error as InnerError;
 
+#[cfg(feature = "tokio-codec")]
+use {
+    super::stream,
+    bytes::{buf::BufMutExt, Buf, BytesMut},
+    serde::de::Error as _,
+};
+
+use super::{generic, raw};
 use crate::io::{Read, Write};
 use crate::message::{self as msg, Message, MessageError};
 use crate::serde::{ArrayDecoder, ArrayEncoder, ArrayFieldDecoder, ArrayFieldEncoder};
-use crate::types::{BasicValue, ConcreteBasicValue, FromBasicValuePart, IntoBasicValue, KnownKind};
+use crate::types::{
+    BasicType, BasicValue, ConcreteBasicValue, FromBasicValuePart, IntoBasicValue, KnownKind,
+};
 
 pub use serde_json::Value;
 
@@ -114,6 +131,159 @@ where
     }
 }
 
+/// A `tokio_util` codec for messages of type `T`, encoded as JSON arrays
+/// with no additional framing.
+#[cfg(feature = "tokio-codec")]
+pub type MessageCodec<T> = stream::MessageCodec<RawFormat, T>;
+
+/// The state of a scan for the end of a JSON array.
+#[cfg(feature = "tokio-codec")]
+#[derive(Debug, Default)]
+pub struct Scanner {
+    offset: usize,
+    depth: usize,
+    in_str: bool,
+    escaped: bool,
+}
+
+#[cfg(feature = "tokio-codec")]
+impl stream::Format for RawFormat {
+    type Map = Map;
+    type Val = Val;
+    type Error = InnerError;
+    type Scanner = Scanner;
+
+    fn scan(s: &mut Scanner, src: &mut BytesMut) -> Result<Option<BytesMut>, InnerError> {
+        while s.offset < src.len() {
+            let b = src[s.offset];
+            if s.depth == 0 {
+                match b {
+                    // Whitespace between messages is dropped.
+                    b' ' | b'\t' | b'\n' | b'\r' => src.advance(1),
+                    b'[' => {
+                        s.offset += 1;
+                        s.depth = 1;
+                    }
+                    _ => return Err(InnerError::custom("expected array")),
+                }
+                continue;
+            }
+            s.offset += 1;
+            if s.in_str {
+                if s.escaped {
+                    s.escaped = false;
+                } else if b == b'\\' {
+                    s.escaped = true;
+                } else if b == b'"' {
+                    s.in_str = false;
+                }
+                continue;
+            }
+            match b {
+                b'"' => s.in_str = true,
+                b'[' | b'{' => s.depth += 1,
+                b']' | b'}' => {
+                    s.depth -= 1;
+                    if s.depth == 0 {
+                        let len = s.offset;
+                        *s = Scanner::default();
+                        return Ok(Some(src.split_to(len)));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+
+    fn decode<T>(frame: &[u8]) -> Result<T, Error>
+    where
+        T: Message<Map, Val>,
+    {
+        let mut deserializer = Deserializer::from_slice(frame);
+        T::decode(ArrayDecoder::new(&mut deserializer))
+    }
+
+    fn encode<T>(item: T, dst: &mut BytesMut) -> Result<(), Error>
+    where
+        T: Message<Map, Val>,
+    {
+        let mut serializer = Serializer::new(dst.writer());
+        item.encode(ArrayEncoder::new(&mut serializer))
+    }
+}
+
+/// The JSON encoding for [`raw`] decoding and stream codecs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawFormat;
+
+/// A JSON map or value, decoded on demand.
+pub type Raw = raw::Raw<RawFormat>;
+
+/// Decodes a JSON message sharing its buffer.
+pub type BytesDecoder = raw::BytesDecoder<RawFormat>;
+
+impl raw::Format for RawFormat {
+    type Error = InnerError;
+
+    fn split_array(src: &Bytes) -> Result<VecDeque<Bytes>, Self::Error> {
+        let items: Vec<&RawValue> = serde_json::from_slice(src)?;
+        Ok(items
+            .into_iter()
+            .map(|item| src.slice_ref(item.get().as_bytes()))
+            .collect())
+    }
+
+    fn basic_type(item: &[u8]) -> BasicType {
+        match item.first() {
+            Some(b'"') => BasicType::Str,
+            Some(b'{') => BasicType::Map,
+            Some(_) if item.iter().all(u8::is_ascii_digit) => BasicType::U64,
+            _ => BasicType::Val,
+        }
+    }
+
+    fn decode_u64(item: &[u8]) -> Result<u64, Self::Error> {
+        serde_json::from_slice(item)
+    }
+
+    fn decode_str(item: Bytes) -> Result<ByteString, Self::Error> {
+        if item.len() >= 2 && !item.contains(&b'\\') {
+            if let Some(s) = raw::share_str(item.slice(1..item.len() - 1)) {
+                return Ok(s);
+            }
+        }
+        serde_json::from_slice::<String>(&item).map(ByteString::from)
+    }
+
+    fn decode<T: DeserializeOwned>(item: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(item)
+    }
+
+    fn serialize<S: ser::Serializer>(item: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let raw: &RawValue = serde_json::from_slice(item).map_err(ser::Error::custom)?;
+        raw.serialize(serializer)
+    }
+}
+
+impl raw::FromRaw<RawFormat> for Value {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
+impl raw::FromRaw<RawFormat> for Map {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
+impl raw::FromRaw<RawFormat> for generic::Map<Val> {
+    fn from_raw(raw: Raw) -> Result<Self, InnerError> {
+        raw.parse()
+    }
+}
+
 impl<B> IntoBasicValue<B, Map, Val> for Value
 where
     B: BasicValue<Map, Val>,
@@ -122,22 +292,42 @@ where
     type Error = B::Error;
 
     fn into_basic(self) -> Result<B, Self::Error> {
-        match self {
-            Value::Number(n) if n.is_u64() => {
-                if let Some(n) = n.as_u64() {
-                    if n <= u8::max_value() as u64 {
-                        B::from_basic_u8(n as u8)
-                    } else {
-                        B::from_basic_u64(n)
-                    }
+        value_into_basic(self)
+    }
+}
+
+impl<B> IntoBasicValue<B, generic::Map<Val>, Val> for Value
+where
+    B: BasicValue<generic::Map<Val>, Val>,
+    B: FromBasicValuePart<generic::Map<Val>, Val>,
+{
+    type Error = B::Error;
+
+    fn into_basic(self) -> Result<B, Self::Error> {
+        value_into_basic(self)
+    }
+}
+
+fn value_into_basic<B, M>(value: Value) -> Result<B, B::Error>
+where
+    B: FromBasicValuePart<M, Val>,
+    M: From<Map>,
+{
+    match value {
+        Value::Number(n) if n.is_u64() => {
+            if let Some(n) = n.as_u64() {
+                if n <= u8::max_value() as u64 {
+                    B::from_basic_u8(n as u8)
                 } else {
-                    unreachable!()
+                    B::from_basic_u64(n)
                 }
+            } else {
+                unreachable!()
             }
-            Value::String(s) => B::from_basic_str(s),
-            Value::Object(m) => B::from_basic_map(m),
-            val => B::from_basic_val(val),
         }
+        Value::String(s) => B::from_basic_str(s),
+        Value::Object(m) => B::from_basic_map(m.into()),
+        val => B::from_basic_val(val),
     }
 }
 
@@ -147,8 +337,9 @@ mod tests {
     use bytes::BytesMut;
 
     use super::*;
-    use crate::message::{HelloMessage, Message, StandardMessage};
-    use crate::types::{Body, Meta};
+    use crate::bus::BusMessage;
+    use crate::message::{GenericMessage, HelloMessage, Message, StandardMessage};
+    use crate::types::{Body, CustomKind, Meta};
 
     #[test]
     fn test_message_encoder_decoder() {
@@ -172,4 +363,104 @@ mod tests {
             other => panic!("unexpected message {:?}", other),
         }
     }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_message_codec_partial() {
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = MessageCodec::<StandardMessage<Map, Val>>::new();
+        let mut buf = BytesMut::new();
+        for body in &["1", "2"] {
+            let message = HelloMessage::new(
+                Body::new(Value::String(body.to_string())),
+                Meta::new(Map::default()),
+            );
+            let message = message.into_standard().unwrap();
+            codec.encode(message, &mut buf).unwrap();
+        }
+        let mut src = buf.split_to(3);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.unsplit(buf);
+        for _ in 0..2 {
+            match codec.decode(&mut src).unwrap() {
+                Some(StandardMessage::Hello(_)) => (),
+                other => panic!("unexpected message {:?}", other),
+            }
+        }
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(src.is_empty());
+    }
+
+    #[cfg(feature = "tokio-codec")]
+    #[test]
+    fn test_message_codec_max_length() {
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let message = || {
+            let message = HelloMessage::new(Body::new(Value::from("1")), Meta::new(Map::default()));
+            message.into_standard().unwrap()
+        };
+        let mut buf = BytesMut::new();
+        MessageCodec::<StandardMessage<Map, Val>>::new()
+            .encode(message(), &mut buf)
+            .unwrap();
+        let len = buf.len();
+
+        let mut codec = MessageCodec::<StandardMessage<Map, Val>>::with_max_length(len);
+        assert!(codec.decode(&mut buf.clone()).unwrap().is_some());
+
+        // Complete or not, a message is refused once longer than the max.
+        codec.set_max_length(len - 1);
+        assert!(matches!(
+            codec.decode(&mut buf.clone()),
+            Err(MessageError::Custom(_))
+        ));
+        let mut codec = MessageCodec::<StandardMessage<Map, Val>>::with_max_length(len - 2);
+        assert!(matches!(
+            codec.decode(&mut BytesMut::from(&buf[..len - 1])),
+            Err(MessageError::Custom(_))
+        ));
+
+        let mut dst = BytesMut::new();
+        assert!(matches!(
+            codec.encode(message(), &mut dst),
+            Err(MessageError::Custom(_))
+        ));
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn test_bytes_decoder_shares_buffer() {
+        let src = Bytes::from_static(br#"[10,1,"test.echo",{"a":1},{}]"#);
+        let message = StandardMessage::<Raw, Raw>::decode(BytesDecoder::new(src.clone())).unwrap();
+        let call = match message {
+            StandardMessage::Call(call) => call,
+            other => panic!("unexpected message {:?}", other),
+        };
+        let uri = call.uri.as_str().as_bytes();
+        assert_eq!(uri, b"test.echo");
+        assert_eq!(uri.as_ptr(), src[7..].as_ptr());
+        assert_eq!(call.body.as_inner().as_bytes(), br#"{"a":1}"#);
+        let body: Value = call.body.as_inner().parse().unwrap();
+        assert_eq!(body["a"], 1);
+        // Raw values are written back as they were read
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = MessageEncoder::from_writer(&mut writer);
+        call.encode(&mut encoder).unwrap();
+        assert_eq!(&src[..], &writer.into_inner()[..]);
+    }
+
+    #[test]
+    fn test_decode_custom_kind() {
+        let kind = CustomKind::new("TEST_JSON", 210, 1, Some(1));
+        kind.register().unwrap();
+        let src = br#"[210,"x"]"#;
+        let mut decoder = MessageDecoder::from_reader(&src[..]);
+        let message = GenericMessage::<Map, Val>::decode(&mut decoder).unwrap();
+        assert_eq!(message.kind(), KnownKind::Custom(kind));
+        let mut decoder = MessageDecoder::from_reader(&src[..]);
+        let message = BusMessage::<Val>::decode(&mut decoder).unwrap();
+        assert_eq!(message.kind(), KnownKind::Custom(kind));
+    }
 }
@@ -1,3 +1,7 @@
 pub mod cbor;
 pub mod generic;
 pub mod json;
+pub mod msgpack;
+pub mod raw;
+#[cfg(feature = "tokio-codec")]
+pub mod stream;
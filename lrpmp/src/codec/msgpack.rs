@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use rmp_serde::decode::{self, Deserializer, ReadReader};
+use rmp_serde::encode::{self, Serializer};
+
+use super::generic;
+use crate::io::{Read, Write};
+use crate::message::{self as msg, Message, MessageError};
+use crate::serde::{ArrayDecoder, ArrayEncoder, ArrayFieldDecoder, ArrayFieldEncoder};
+use crate::types::{BasicValue, ConcreteBasicValue, FromBasicValuePart, IntoBasicValue, KnownKind};
+
+pub use rmpv::Value;
+
+pub type Map = BTreeMap<String, Val>;
+pub type Val = Value;
+
+/// Unlike JSON and CBOR, MessagePack has separate encode and decode errors.
+pub type EncodeError = MessageError<encode::Error>;
+pub type DecodeError = MessageError<decode::Error>;
+
+pub struct MessageEncoder<W: Write> {
+    inner: Serializer<W>,
+}
+
+impl<W: Write> MessageEncoder<W> {
+    pub fn from_writer(writer: W) -> Self {
+        Self {
+            inner: Serializer::new(writer),
+        }
+    }
+}
+
+impl<'a, M, V, W> msg::MessageEncoder<M, V> for &'a mut MessageEncoder<W>
+where
+    W: Write,
+    M: Serialize,
+    V: Serialize,
+{
+    type Ok = ();
+    type Error = encode::Error;
+    type FieldEncoder = ArrayFieldEncoder<&'a mut Serializer<W>>;
+
+    fn start(self, kind: KnownKind) -> Result<Self::FieldEncoder, MessageError<Self::Error>> {
+        msg::MessageEncoder::<M, V>::start(ArrayEncoder::new(&mut self.inner), kind)
+    }
+}
+
+pub struct MessageDecoder<R: Read> {
+    inner: Deserializer<ReadReader<R>>,
+}
+
+impl<R: Read> MessageDecoder<R> {
+    pub fn from_reader(reader: R) -> Self {
+        Self {
+            inner: Deserializer::new(reader),
+        }
+    }
+}
+
+impl<'a, M, V, R> msg::MessageDecoder<M, V> for &'a mut MessageDecoder<R>
+where
+    R: Read,
+    M: Deserialize<'a>,
+    V: Deserialize<'a>,
+    V: IntoBasicValue<ConcreteBasicValue<M, V>, M, V>,
+    V::Error: Into<MessageError<decode::Error>>,
+{
+    type Error = decode::Error;
+    type FieldDecoder = ArrayFieldDecoder<M, V, decode::Error>;
+
+    fn start(self) -> Result<(KnownKind, Self::FieldDecoder), MessageError<Self::Error>> {
+        msg::MessageDecoder::<M, V>::start(ArrayDecoder::new(&mut self.inner))
+    }
+}
+
+pub struct MessageWriter<W: Write> {
+    inner: Serializer<W>,
+}
+
+impl<W> msg::MessageWriter<W> for MessageWriter<W>
+where
+    W: Write,
+{
+    type Map = Map;
+    type Val = Val;
+    type Error = EncodeError;
+
+    fn write_message<M>(&mut self, message: &M) -> Result<(), Self::Error>
+    where
+        M: Message<Self::Map, Self::Val>,
+    {
+        message.encode_ref(ArrayEncoder::new(&mut self.inner))
+    }
+}
+
+pub struct MessageReader<R>
+where
+    R: Read,
+{
+    inner: Deserializer<ReadReader<R>>,
+}
+
+impl<R> msg::MessageReader<R> for MessageReader<R>
+where
+    R: Read,
+{
+    type Map = Map;
+    type Val = Val;
+    type Error = DecodeError;
+
+    fn read_message<M>(&mut self) -> Result<M, Self::Error>
+    where
+        M: Message<Self::Map, Self::Val>,
+    {
+        M::decode(ArrayDecoder::new(&mut self.inner))
+    }
+}
+
+fn all_keys_are_string(map: &[(Value, Value)]) -> bool {
+    map.iter().all(|(k, _)| k.is_str())
+}
+
+impl<B> IntoBasicValue<B, Map, Val> for Value
+where
+    B: BasicValue<Map, Val>,
+    B: FromBasicValuePart<Map, Val>,
+{
+    type Error = B::Error;
+
+    fn into_basic(self) -> Result<B, Self::Error> {
+        value_into_basic(self)
+    }
+}
+
+impl<B> IntoBasicValue<B, generic::Map<Val>, Val> for Value
+where
+    B: BasicValue<generic::Map<Val>, Val>,
+    B: FromBasicValuePart<generic::Map<Val>, Val>,
+{
+    type Error = B::Error;
+
+    fn into_basic(self) -> Result<B, Self::Error> {
+        value_into_basic(self)
+    }
+}
+
+fn value_into_basic<B, M>(value: Value) -> Result<B, B::Error>
+where
+    B: FromBasicValuePart<M, Val>,
+    M: From<Map>,
+{
+    match value {
+        Value::Integer(i) => match i.as_u64() {
+            Some(n) if n <= u64::from(u8::MAX) => B::from_basic_u8(n as u8),
+            Some(n) => B::from_basic_u64(n),
+            None => B::from_basic_val(Value::Integer(i)),
+        },
+        Value::String(s) if s.is_str() => B::from_basic_str(s.into_str().unwrap()),
+        Value::Map(src_map) if all_keys_are_string(&src_map) => {
+            let iter = src_map.into_iter().map(|(k, v)| {
+                if let Value::String(k) = k {
+                    (k.into_str().unwrap(), v)
+                } else {
+                    unreachable!()
+                }
+            });
+            B::from_basic_map(iter.collect::<Map>().into())
+        }
+        val => B::from_basic_val(val),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::buf::{BufExt, BufMutExt};
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::message::{HelloMessage, Message, StandardMessage};
+    use crate::types::{Body, Meta};
+
+    #[test]
+    fn test_message_encoder_decoder() {
+        let src_message = HelloMessage::new(Body::new(Value::from("1")), Meta::new(Map::default()));
+        // Encoder
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = MessageEncoder::from_writer(&mut writer);
+        src_message.encode(&mut encoder).unwrap();
+        // Buf
+        let buf = writer.into_inner();
+        assert_eq!(&[0x93, 0x02, 0xA1, 0x31, 0x80][..], &buf[..]);
+        // Decoder
+        let reader = buf.reader();
+        let mut decoder = MessageDecoder::from_reader(reader);
+        let message = StandardMessage::<Map, Val>::decode(&mut decoder).unwrap();
+        match message {
+            StandardMessage::Hello(m) => assert_eq!(m.body.into_inner(), Value::from("1")),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_small_integers_are_u8() {
+        use crate::types::BasicType;
+
+        let basic: ConcreteBasicValue<Map, Val> = Value::from(2).into_basic().unwrap();
+        assert_eq!(basic.ty(), BasicType::U8);
+        let basic: ConcreteBasicValue<Map, Val> = Value::from(256).into_basic().unwrap();
+        assert_eq!(basic.ty(), BasicType::U64);
+    }
+}
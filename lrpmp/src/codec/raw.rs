@@ -0,0 +1,244 @@
+//! Decoding messages straight from a shared buffer.
+//!
+//! A [`BytesDecoder`] splits an encoded message into the encoded bytes of
+//! each field, each a cheap slice of the source [`Bytes`]. String fields
+//! such as [`Uri`](crate::types::Uri) share the buffer where the encoding
+//! allows, and [`Raw`] maps and values are only decoded once parsed.
+//! URIs within a parsed map or value share the buffer too, where the
+//! encoding lends the string out.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use bytestring::ByteString;
+use serde::de::DeserializeOwned;
+use serde::ser::{Serialize, Serializer};
+
+use crate::message::dec::*;
+use crate::message::*;
+use crate::types::*;
+
+/// An encoding that can be split into fields without decoding them.
+pub trait Format {
+    type Error;
+
+    /// Splits an encoded message array into its encoded elements.
+    fn split_array(src: &Bytes) -> Result<VecDeque<Bytes>, Self::Error>;
+
+    /// Returns the basic type of an encoded element.
+    ///
+    /// Unsigned integers are reported as `U64` and anything that is not
+    /// an unsigned integer, string or map as `Val`.
+    fn basic_type(item: &[u8]) -> BasicType;
+
+    fn decode_u64(item: &[u8]) -> Result<u64, Self::Error>;
+
+    /// Decodes an encoded string, sharing `item` if it needs no unescaping.
+    fn decode_str(item: Bytes) -> Result<ByteString, Self::Error>;
+
+    fn decode<T: DeserializeOwned>(item: &[u8]) -> Result<T, Self::Error>;
+
+    fn serialize<S: Serializer>(item: &[u8], serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+/// An encoded map or value, decoded on demand.
+pub struct Raw<F> {
+    bytes: Bytes,
+    format: PhantomData<F>,
+}
+
+impl<F> Raw<F> {
+    pub(crate) fn new(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            format: PhantomData,
+        }
+    }
+
+    /// Returns the encoded bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.bytes
+    }
+}
+
+impl<F: Format> Raw<F> {
+    /// Decodes the value.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, F::Error> {
+        with_source(&self.bytes, || F::decode(self.as_bytes()))
+    }
+}
+
+impl<F> Clone for Raw<F> {
+    fn clone(&self) -> Self {
+        Self::new(self.bytes.clone())
+    }
+}
+
+impl<F> fmt::Debug for Raw<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Raw").field(&self.bytes).finish()
+    }
+}
+
+impl<F: Format> Serialize for Raw<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        F::serialize(self.as_bytes(), serializer)
+    }
+}
+
+/// Conversion from an encoded map or value.
+pub trait FromRaw<F: Format>: Sized {
+    fn from_raw(raw: Raw<F>) -> Result<Self, F::Error>;
+}
+
+impl<F: Format> FromRaw<F> for Raw<F> {
+    fn from_raw(raw: Raw<F>) -> Result<Self, F::Error> {
+        Ok(raw)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Decodes a message from a buffer holding exactly one message.
+pub struct BytesDecoder<F> {
+    src: Bytes,
+    format: PhantomData<F>,
+}
+
+impl<F> BytesDecoder<F> {
+    pub fn new(src: Bytes) -> Self {
+        Self {
+            src,
+            format: PhantomData,
+        }
+    }
+}
+
+impl<F, M, V> MessageDecoder<M, V> for BytesDecoder<F>
+where
+    F: Format,
+    M: FromRaw<F>,
+    V: FromRaw<F>,
+{
+    type Error = F::Error;
+    type FieldDecoder = BytesFieldDecoder<F, M, V>;
+
+    fn start(self) -> Result<(KnownKind, Self::FieldDecoder), MessageError<F::Error>> {
+        let items = F::split_array(&self.src).map_err(MessageError::Codec)?;
+        let mut field_decoder = BytesFieldDecoder {
+            items,
+            marker: PhantomData,
+        };
+        let kind = field_decoder.decode_field(Some("kind"))?;
+        Ok((kind, field_decoder))
+    }
+}
+
+pub struct BytesFieldDecoder<F, M, V> {
+    items: VecDeque<Bytes>,
+    marker: PhantomData<(F, M, V)>,
+}
+
+impl<F, M, V> MessageFieldDecoder<M, V> for BytesFieldDecoder<F, M, V>
+where
+    F: Format,
+    M: FromRaw<F>,
+    V: FromRaw<F>,
+{
+    type Error = F::Error;
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+
+    fn decode_field<T>(&mut self, _name: Option<&'static str>) -> Result<T, MessageError<F::Error>>
+    where
+        T: FromBasicValuePart<M, V>,
+        T::Error: Into<MessageError<F::Error>>,
+    {
+        let item = self.items.pop_front().ok_or(MessageError::Eof)?;
+        let ty = if T::expected_types() == [BasicType::Val] {
+            BasicType::Val
+        } else {
+            F::basic_type(&item)
+        };
+        match ty {
+            BasicType::U8 | BasicType::U64 => {
+                let v = F::decode_u64(&item).map_err(MessageError::Codec)?;
+                if v <= u64::from(u8::MAX) {
+                    T::from_basic_u8(v as u8)
+                } else {
+                    T::from_basic_u64(v)
+                }
+            }
+            BasicType::Str => {
+                let v = F::decode_str(item).map_err(MessageError::Codec)?;
+                T::from_basic_byte_str(v)
+            }
+            BasicType::Map => {
+                let v = M::from_raw(Raw::new(item)).map_err(MessageError::Codec)?;
+                T::from_basic_map(v)
+            }
+            BasicType::Val => {
+                let v = V::from_raw(Raw::new(item)).map_err(MessageError::Codec)?;
+                T::from_basic_val(v)
+            }
+        }
+        .map_err(Into::into)
+    }
+}
+
+/// Shares `contents` as a `ByteString` if it is valid UTF-8.
+pub(crate) fn share_str(contents: Bytes) -> Option<ByteString> {
+    if std::str::from_utf8(&contents).is_ok() {
+        // Safety: validated as UTF-8 above.
+        Some(unsafe { ByteString::from_bytes_unchecked(contents) })
+    } else {
+        None
+    }
+}
+
+thread_local! {
+    /// The buffer being parsed on this thread, if any.
+    static SOURCE: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `src` as the buffer borrowed strings may be shared from.
+fn with_source<T>(src: &Bytes, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Bytes>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            SOURCE.with(|source| *source.borrow_mut() = prev);
+        }
+    }
+
+    let prev = SOURCE.with(|source| source.replace(Some(src.clone())));
+    let _restore = Restore(prev);
+    f()
+}
+
+/// Shares a string borrowed from the buffer being parsed, if it is one.
+pub(crate) fn share_borrowed(s: &str) -> Option<Bytes> {
+    if s.is_empty() {
+        return None;
+    }
+    SOURCE.with(|source| {
+        let source = source.borrow();
+        let src = source.as_ref()?;
+        let start = src.as_ptr() as usize;
+        let ptr = s.as_ptr() as usize;
+        if ptr >= start && ptr + s.len() <= start + src.len() {
+            Some(src.slice_ref(s.as_bytes()))
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,127 @@
+//! Decoding messages from a stream of bytes with no additional framing.
+//!
+//! A [`MessageCodec`] finds where each message ends by scanning the bytes
+//! as they arrive, picking up where the last scan left off, so a message
+//! arriving in pieces is only scanned once. Each message is decoded once
+//! complete.
+//!
+//! As nothing but the message says how long it is, a codec gives up on a
+//! message longer than its [maximum length](MessageCodec::max_length)
+//! rather than buffering it without bound.
+use std::marker::PhantomData;
+
+use bytes::BytesMut;
+use tokio_util::codec;
+
+use crate::message::{Message, MessageError};
+
+const DEFAULT_MAX_LENGTH: usize = 8 * 1024 * 1024;
+
+const TOO_LONG: &str = "message exceeds max length";
+
+/// An encoding whose messages can be found in a stream of bytes.
+pub trait Format {
+    type Map;
+    type Val;
+    type Error;
+
+    /// The state of a scan between calls.
+    type Scanner: Default;
+
+    /// Scans on from where the last call left off, splitting the first
+    /// message off `src` once complete and resetting the scanner.
+    fn scan(
+        scanner: &mut Self::Scanner,
+        src: &mut BytesMut,
+    ) -> Result<Option<BytesMut>, Self::Error>;
+
+    fn decode<T>(frame: &[u8]) -> Result<T, MessageError<Self::Error>>
+    where
+        T: Message<Self::Map, Self::Val>;
+
+    fn encode<T>(item: T, dst: &mut BytesMut) -> Result<(), MessageError<Self::Error>>
+    where
+        T: Message<Self::Map, Self::Val>;
+}
+
+/// A `tokio_util` codec for messages of type `T`, encoded in the format
+/// `F` with no additional framing.
+///
+/// Decoding never blocks: `None` is returned until the buffer holds a
+/// complete message.
+pub struct MessageCodec<F: Format, T> {
+    scanner: F::Scanner,
+    max_length: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<F: Format, T> MessageCodec<F, T> {
+    /// Constructs a codec with the default maximum length of 8 MiB.
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Constructs a codec for messages of at most `max_length` bytes.
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            scanner: F::Scanner::default(),
+            max_length,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the maximum length of a message in bytes.
+    ///
+    /// Decoding fails once the buffer holds more bytes of a message than
+    /// this, complete or not, and encoding fails on a longer message.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+}
+
+impl<F: Format, T> Default for MessageCodec<F, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F, T> codec::Decoder for MessageCodec<F, T>
+where
+    F: Format,
+    T: Message<F::Map, F::Val>,
+{
+    type Item = T;
+    type Error = MessageError<F::Error>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, Self::Error> {
+        match F::scan(&mut self.scanner, src).map_err(MessageError::Codec)? {
+            Some(frame) if frame.len() > self.max_length => Err(MessageError::Custom(TOO_LONG)),
+            Some(frame) => F::decode(&frame).map(Some),
+            // The buffer holds nothing but the start of the next message.
+            None if src.len() > self.max_length => Err(MessageError::Custom(TOO_LONG)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<F, T> codec::Encoder<T> for MessageCodec<F, T>
+where
+    F: Format,
+    T: Message<F::Map, F::Val>,
+{
+    type Error = MessageError<F::Error>;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        F::encode(item, dst)?;
+        if dst.len() - start > self.max_length {
+            dst.truncate(start);
+            return Err(MessageError::Custom(TOO_LONG));
+        }
+        Ok(())
+    }
+}
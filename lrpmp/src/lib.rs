@@ -1,5 +1,8 @@
 use proc_macro_hack::proc_macro_hack;
 
+// Allows code generated by `lrpmp-macros` to refer to `::lrpmp` within this crate.
+extern crate self as lrpmp;
+
 pub mod bus;
 pub mod codec;
 pub mod io;
@@ -9,16 +12,24 @@ pub mod types;
 
 /// Returns a valid URI given a static str.
 ///
+/// Several strs are joined as segments, so a URI can be derived from the
+/// namespace of a service.
+///
 /// # Example
 /// ```rust
 /// use lrpmp::uri;
 /// use lrpmp::types::Uri;
 ///
 /// static MY_URI: Uri = uri!("hello.world");
+/// static MY_ECHO_URI: Uri = uri!("hello.world", "echo");
+///
+/// assert_eq!(MY_ECHO_URI.as_str(), "hello.world.echo");
 /// ```
 #[proc_macro_hack]
 pub use ::lrpmp_macros::uri;
 
+::lrpmp_macros::impl_spec_version!();
+
 pub mod uris {
     use crate::types::Uri;
 
@@ -1,10 +1,12 @@
 use std::convert::Infallible;
+use std::io;
 
 use crate::types::{Kind, KnownKindFromBasicError, UnexpectedType, UriFromBasicError};
 
 #[derive(Debug)]
 pub enum MessageError<E> {
     Eof,
+    Io(io::Error),
     Codec(E),
     Uri(UriFromBasicError),
     UnexpectedKind(Kind),
@@ -17,6 +19,7 @@ impl<E> MessageError<E> {
         use MessageError::*;
         match err {
             Eof => Eof,
+            Io(e) => Io(e),
             Uri(u) => Uri(u),
             Codec(()) => Custom("unspecified codec error"),
             UnexpectedKind(k) => UnexpectedKind(k),
@@ -26,6 +29,12 @@ impl<E> MessageError<E> {
     }
 }
 
+impl<E> From<io::Error> for MessageError<E> {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 impl<E> From<KnownKindFromBasicError> for MessageError<E> {
     fn from(err: KnownKindFromBasicError) -> Self {
         match err {
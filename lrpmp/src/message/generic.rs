@@ -3,7 +3,7 @@ use std::slice;
 use super::dec::*;
 use super::enc::*;
 use super::*;
-use crate::types::{BasicValue, BasicValueExt, ConcreteBasicValue, KnownKind};
+use crate::types::{BasicValue, BasicValueExt, ConcreteBasicValue, Kind, KnownKind};
 
 #[derive(Debug, Clone)]
 pub struct GenericMessage<M, V> {
@@ -23,6 +23,21 @@ impl<M, V> GenericMessage<M, V> {
         Self { kind, fields }
     }
 
+    /// Converts the message into a typed custom message of the same kind.
+    ///
+    /// Fields keep the basic type they were decoded as, so a body decoded
+    /// generically as a string or map will not convert to a `Body`; decode
+    /// the typed message directly where that matters.
+    pub fn into_custom<T>(self) -> Result<T, MessageError<()>>
+    where
+        T: CustomMessage<M, V>,
+    {
+        if self.kind != KnownKind::Custom(T::KIND) {
+            return Err(MessageError::UnexpectedKind(Kind::Known(self.kind)));
+        }
+        self.transmute()
+    }
+
     pub fn field_iter(&self) -> FieldIter<'_, M, V> {
         FieldIter {
             inner: self.fields.iter(),
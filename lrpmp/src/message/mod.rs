@@ -5,18 +5,28 @@ mod transmute;
 pub mod dec;
 pub mod enc;
 pub mod generic;
+pub mod schema;
 
 pub use self::dec::MessageDecoder;
 pub use self::enc::MessageEncoder;
 pub use self::error::*;
 pub use self::generic::GenericMessage;
 pub use self::io::*;
+pub use self::schema::{Schema, SchemaError};
 
 pub use crate::std_msgs::*;
 
+/// Derives [`Message`] and [`CustomMessage`] for a struct with named fields.
+///
+/// The kind is given with `#[lrpmp(kind = "MY_KIND", code = 200)]` and
+/// fields are encoded in order. Trailing `Option` fields are optional and
+/// left off the wire when `None`. The struct may declare the map and value
+/// type parameters `M` and `V` for its fields.
+pub use lrpmp_macros::Message;
+
 use self::transmute::*;
 
-use crate::types::KnownKind;
+use crate::types::{CustomKind, KnownKind};
 
 pub trait Message<M, V>: Sized {
     /// Returns the message kind.
@@ -51,6 +61,14 @@ pub trait Message<M, V>: Sized {
     }
 }
 
+/// A typed message of a custom kind.
+///
+/// The kind must be [registered](CustomKind::register) for messages of it
+/// to be decoded.
+pub trait CustomMessage<M, V>: Message<M, V> {
+    const KIND: CustomKind;
+}
+
 pub trait MessageExt<M, V>: Message<M, V> {
     #[inline]
     fn is_standard(&self) -> bool {
@@ -69,3 +87,51 @@ pub trait MessageExt<M, V>: Message<M, V> {
 }
 
 impl<T, M, V> MessageExt<M, V> for T where T: Message<M, V> {}
+
+#[cfg(test)]
+mod tests {
+    use bytes::buf::{BufExt, BufMutExt};
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::codec::json::{self, Value};
+    use crate::types::{Body, Id, Meta, Uri};
+
+    #[derive(Debug, Message)]
+    #[lrpmp(kind = "TEST_PING", code = 220)]
+    struct PingMessage<M, V> {
+        id: Id,
+        uri: Uri,
+        body: Body<V>,
+        meta: Option<Meta<M, V>>,
+    }
+
+    #[test]
+    fn test_derive_message() {
+        <PingMessage<json::Map, Value> as CustomMessage<_, _>>::KIND
+            .register()
+            .unwrap();
+        let src_message = PingMessage::<json::Map, Value> {
+            id: Id::new(1),
+            uri: Uri::from_static("test.ping").unwrap(),
+            body: Body::new(Value::Bool(true)),
+            meta: None,
+        };
+        let mut writer = BytesMut::new().writer();
+        let mut encoder = json::MessageEncoder::from_writer(&mut writer);
+        src_message.encode(&mut encoder).unwrap();
+        let buf = writer.into_inner();
+        assert_eq!(br#"[220,1,"test.ping",true]"#, &buf[..]);
+
+        let mut decoder = json::MessageDecoder::from_reader(&buf[..]);
+        let message = PingMessage::<json::Map, Value>::decode(&mut decoder).unwrap();
+        assert_eq!(u64::from(message.id), 1);
+        assert_eq!(message.uri.as_str(), "test.ping");
+        assert!(message.meta.is_none());
+
+        let mut decoder = json::MessageDecoder::from_reader(buf.reader());
+        let message = GenericMessage::<json::Map, Value>::decode(&mut decoder).unwrap();
+        let message: PingMessage<_, _> = message.into_custom().unwrap();
+        assert_eq!(message.body.into_inner(), Value::Bool(true));
+    }
+}
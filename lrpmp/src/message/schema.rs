@@ -0,0 +1,381 @@
+//! Validation of generic messages against a spec loaded at runtime.
+//!
+//! A [`Schema`] checks a [`GenericMessage`] has a kind defined by the spec,
+//! the number of fields the kind allows and a basic type for each field its
+//! declared type can be read from, without generating code for the spec.
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use lrpmp_spec::{MsgDef, Spec};
+
+use super::{GenericMessage, Message};
+use crate::types::*;
+
+/// The message definitions of a spec, ready to validate messages with.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    spec: Spec,
+    messages: HashMap<u8, MsgSchema>,
+}
+
+#[derive(Debug, Clone)]
+struct MsgSchema {
+    name: String,
+    fields_min: usize,
+    fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Clone)]
+struct FieldSchema {
+    name: String,
+    ty: String,
+    expected: &'static [BasicType],
+}
+
+impl Schema {
+    /// Constructs a schema from a spec, which must be valid.
+    ///
+    /// The spec's kinds that aren't standard are registered as custom kinds
+    /// so messages of them can be decoded, failing if one conflicts with a
+    /// kind already registered.
+    pub fn new(spec: &Spec) -> Result<Self, SchemaError> {
+        let spec = spec.clone().validate()?;
+        for def in spec.message_iter() {
+            register_kind(def)?;
+        }
+        let messages = spec
+            .message_iter()
+            .map(|def| (def.kind_code(), MsgSchema::new(def)))
+            .collect();
+        Ok(Self { spec, messages })
+    }
+
+    /// Loads the spec at `path` and constructs a schema from it.
+    pub fn load<P>(path: P) -> Result<Self, SchemaError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(&Spec::load(path)?)
+    }
+
+    pub fn spec(&self) -> &Spec {
+        &self.spec
+    }
+
+    /// Validates a message against the definition of its kind.
+    ///
+    /// Every violation found is reported, not just the first.
+    pub fn validate<M, V>(&self, message: &GenericMessage<M, V>) -> Result<(), InvalidMessage> {
+        let kind = message.kind();
+        let msg = match self.messages.get(&kind.code()) {
+            Some(msg) => msg,
+            None => {
+                return Err(InvalidMessage {
+                    kind,
+                    violations: vec![Violation::UnknownKind],
+                })
+            }
+        };
+        let mut violations = Vec::new();
+        if msg.name != kind.name() {
+            violations.push(Violation::KindName {
+                expected: msg.name.clone(),
+            });
+        }
+        let actual = message.field_iter().len();
+        if actual < msg.fields_min || actual > msg.fields.len() {
+            violations.push(Violation::FieldCount {
+                min: msg.fields_min,
+                max: msg.fields.len(),
+                actual,
+            });
+        }
+        for (index, (field, value)) in msg.fields.iter().zip(message.field_iter()).enumerate() {
+            let actual = value.ty();
+            if !accepts(field.expected, actual) {
+                violations.push(Violation::FieldType {
+                    index,
+                    name: field.name.clone(),
+                    declared: field.ty.clone(),
+                    expected: field.expected,
+                    actual,
+                });
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidMessage { kind, violations })
+        }
+    }
+}
+
+impl MsgSchema {
+    fn new(def: &MsgDef) -> Self {
+        let fields = def
+            .field_iter()
+            .map(|field| FieldSchema {
+                name: field.name().to_string(),
+                ty: field.ty().to_string(),
+                expected: expected_types(field.ty()),
+            })
+            .collect();
+        Self {
+            name: def.kind_name().to_string(),
+            fields_min: def.field_iter().filter(|f| !f.is_optional()).count(),
+            fields,
+        }
+    }
+}
+
+/// Registers the kind of a message definition unless already known.
+fn register_kind(def: &MsgDef) -> Result<(), RegisterKindError> {
+    let fields_min = def.field_iter().filter(|f| !f.is_optional()).count();
+    let fields_max = Some(def.field_iter().len());
+    match KnownKind::from_code(def.kind_code()) {
+        Some(KnownKind::Standard(kind)) if kind.name() == def.kind_name() => return Ok(()),
+        Some(KnownKind::Custom(kind))
+            if kind.name() == def.kind_name() && kind.field_count() == (fields_min, fields_max) =>
+        {
+            return Ok(())
+        }
+        _ => (),
+    }
+    // Registered kinds live for the rest of the process, as does their name.
+    let name = Box::leak(def.kind_name().to_string().into_boxed_str());
+    CustomKind::new(name, def.kind_code(), fields_min, fields_max).register()
+}
+
+/// Returns the basic types a field of a declared type is read from.
+///
+/// The spec is validated first, so the type is known.
+fn expected_types(ty: &str) -> &'static [BasicType] {
+    match ty {
+        "Id" => <Id as FromBasicValuePart<(), ()>>::expected_types(),
+        "Uri" => <Uri as FromBasicValuePart<(), ()>>::expected_types(),
+        "Kind" => <Kind as FromBasicValuePart<(), ()>>::expected_types(),
+        "Meta" => <Meta<(), ()> as FromBasicValuePart<(), ()>>::expected_types(),
+        "Body" => <Body<()> as FromBasicValuePart<(), ()>>::expected_types(),
+        _ => unreachable!("unknown type: {}", ty),
+    }
+}
+
+fn accepts(expected: &[BasicType], actual: BasicType) -> bool {
+    expected.contains(&actual)
+        // A `u8` is widened where a `u64` is read.
+        || (actual == BasicType::U8 && expected.contains(&BasicType::U64))
+        // Values are decoded generically as whatever basic type they are.
+        || expected.contains(&BasicType::Val)
+}
+
+/// Error produced constructing a [`Schema`].
+#[derive(Debug)]
+pub enum SchemaError {
+    Spec(lrpmp_spec::Error),
+    /// A kind of the spec conflicts with a kind already registered.
+    RegisterKind(RegisterKindError),
+}
+
+impl From<lrpmp_spec::Error> for SchemaError {
+    fn from(err: lrpmp_spec::Error) -> Self {
+        Self::Spec(err)
+    }
+}
+
+impl From<RegisterKindError> for SchemaError {
+    fn from(err: RegisterKindError) -> Self {
+        Self::RegisterKind(err)
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Spec(err) => fmt::Display::fmt(err, f),
+            Self::RegisterKind(RegisterKindError { kind, existing }) => write!(
+                f,
+                "kind `{}` ({}) conflicts with registered kind `{}` ({})",
+                kind.name(),
+                kind.code(),
+                existing.name(),
+                existing.code()
+            ),
+        }
+    }
+}
+
+/// A way a message breaks the definition of its kind.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// The spec defines no message of the kind's code.
+    UnknownKind,
+    /// The spec names the kind of the code differently.
+    KindName { expected: String },
+    FieldCount {
+        min: usize,
+        max: usize,
+        actual: usize,
+    },
+    FieldType {
+        index: usize,
+        name: String,
+        declared: String,
+        expected: &'static [BasicType],
+        actual: BasicType,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownKind => f.write_str("kind not defined by spec"),
+            Self::KindName { expected } => write!(f, "kind named `{}` by spec", expected),
+            Self::FieldCount { min, max, actual } if min == max => {
+                write!(f, "expected {} fields, found {}", min, actual)
+            }
+            Self::FieldCount { min, max, actual } => {
+                write!(f, "expected {} to {} fields, found {}", min, max, actual)
+            }
+            Self::FieldType {
+                index,
+                name,
+                declared,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "field {} `{}` of type `{}` expected {:?}, found {:?}",
+                index, name, declared, expected, actual
+            ),
+        }
+    }
+}
+
+/// Error produced when a message doesn't match its definition in a spec.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidMessage {
+    pub kind: KnownKind,
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for InvalidMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid message of kind {}", self.kind.code())?;
+        for (i, violation) in self.violations.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}", sep, violation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::json::{Map, Value};
+
+    #[test]
+    fn test_schema_validate() {
+        let spec: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 230
+            name = "TEST_SCHEMA"
+            type = "event"
+            stages = ["established"]
+            desc = ""
+            fields = [
+                { name = "uri", type = "Uri", desc = "" },
+                { name = "meta", type = "Meta", desc = "", optional = true },
+            ]
+
+            [[uri_definitions]]
+            uri = "close.normal"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        let schema = Schema::new(&spec).unwrap();
+        // The spec's kind is registered by the schema, once.
+        Schema::new(&spec).unwrap();
+        let kind = KnownKind::from_name("TEST_SCHEMA").unwrap();
+        assert_eq!(kind.code(), 230);
+        assert_eq!(kind.field_count(), (1, Some(2)));
+
+        let valid = GenericMessage::<Map, Value>::new(
+            kind,
+            vec![ConcreteBasicValue::<Map, Value>::Str("close.normal".into())],
+        );
+        schema.validate(&valid).unwrap();
+
+        let invalid = GenericMessage::<Map, Value>::new(
+            kind,
+            vec![
+                ConcreteBasicValue::<Map, Value>::U64(1),
+                ConcreteBasicValue::Str("meta".into()),
+                ConcreteBasicValue::U8(0),
+            ],
+        );
+        let err = schema.validate(&invalid).unwrap_err();
+        assert_eq!(
+            err.violations,
+            vec![
+                Violation::FieldCount {
+                    min: 1,
+                    max: 2,
+                    actual: 3,
+                },
+                Violation::FieldType {
+                    index: 0,
+                    name: "uri".into(),
+                    declared: "Uri".into(),
+                    expected: &[BasicType::Str],
+                    actual: BasicType::U64,
+                },
+                Violation::FieldType {
+                    index: 1,
+                    name: "meta".into(),
+                    declared: "Meta".into(),
+                    expected: &[BasicType::Map],
+                    actual: BasicType::Str,
+                },
+            ]
+        );
+
+        let unknown_kind = CustomKind::new("TEST_SCHEMA_UNKNOWN", 231, 0, Some(0));
+        unknown_kind.register().unwrap();
+        let unknown = GenericMessage::<Map, Value>::new(
+            unknown_kind.into(),
+            Vec::<ConcreteBasicValue<Map, Value>>::new(),
+        );
+        let err = schema.validate(&unknown).unwrap_err();
+        assert_eq!(err.violations, vec![Violation::UnknownKind]);
+
+        // The spec can't take a code another kind was registered with.
+        let conflicting: Spec = r#"
+            version = "0.1.0"
+
+            [[messages]]
+            code = 231
+            name = "TEST_SCHEMA_CONFLICT"
+            type = "event"
+            stages = ["established"]
+            desc = ""
+            fields = []
+
+            [[uri_definitions]]
+            uri = "close.normal"
+            desc = ""
+        "#
+        .parse()
+        .unwrap();
+        match Schema::new(&conflicting) {
+            Err(SchemaError::RegisterKind(err)) => {
+                assert_eq!(err.existing, KnownKind::from(unknown_kind))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
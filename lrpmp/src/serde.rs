@@ -33,12 +33,12 @@ where
     type FieldEncoder = ArrayFieldEncoder<S>;
 
     fn start(self, kind: KnownKind) -> Result<Self::FieldEncoder, MessageError<S::Error>> {
-        let mut seq = self
-            .inner
-            .serialize_seq(kind.field_count().1.map(|c| {
-                c + 1 // account for kind field
-            }))
-            .map_err(MessageError::Codec)?;
+        // The length is only known up front if no fields are optional.
+        let len = match kind.field_count() {
+            (min, Some(max)) if min == max => Some(max + 1), // account for kind field
+            _ => None,
+        };
+        let mut seq = self.inner.serialize_seq(len).map_err(MessageError::Codec)?;
         seq.serialize_element(&kind.code())
             .map_err(MessageError::Codec)?;
         Ok(ArrayFieldEncoder(seq))
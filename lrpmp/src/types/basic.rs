@@ -1,6 +1,7 @@
 use std::convert::Infallible;
 use std::marker::PhantomData;
 
+use bytestring::ByteString;
 use serde::{Deserialize, Serialize};
 
 use super::*;
@@ -220,6 +221,13 @@ pub trait FromBasicValuePart<M, V>: Sized {
         .into())
     }
 
+    /// Constructs the value from a string that may share the buffer it was
+    /// decoded from. Types backed by `ByteString` override this to avoid
+    /// copying.
+    fn from_basic_byte_str(v: ByteString) -> Result<Self, Self::Error> {
+        Self::from_basic_str(v.to_string())
+    }
+
     fn from_basic_map(v: M) -> Result<Self, Self::Error> {
         let _ = v;
         Err(UnexpectedType {
@@ -409,6 +417,10 @@ impl<M, V> Map<M, V> {
         &self.inner
     }
 
+    pub fn as_inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+
     pub fn into_inner(self) -> M {
         self.inner
     }
@@ -14,6 +14,14 @@ impl<V> Body<V> {
             inner: Val::new(val),
         }
     }
+
+    pub fn as_inner(&self) -> &V {
+        self.inner.as_inner()
+    }
+
+    pub fn into_inner(self) -> V {
+        self.inner.into_inner()
+    }
 }
 
 impl<M, V> BasicValue<M, V> for Body<V> {
@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::fmt;
 
 use super::*;
 
@@ -21,6 +22,55 @@ pub struct ParseUriError {
     pub offset: usize,
 }
 
+impl fmt::Display for ParseUriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid uri char `{}` at offset {}",
+            self.invalid, self.offset
+        )
+    }
+}
+
+/// Error produced appending an invalid segment to a URI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UriSegmentError {
+    Empty,
+    /// The URI would have more segments or wildcards than can be counted.
+    TooLong,
+    /// The segment has an invalid char, including a `.`, at the offset
+    /// within the segment.
+    Parse(ParseUriError),
+}
+
+impl From<ParseUriError> for UriSegmentError {
+    fn from(err: ParseUriError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl fmt::Display for UriSegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("empty uri segment"),
+            Self::TooLong => f.write_str("too many uri segments or wildcards"),
+            Self::Parse(err) => write!(f, "{} of segment", err),
+        }
+    }
+}
+
+/// Error produced parsing an unknown match policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseMatchPolicyError {
+    pub policy: String,
+}
+
+impl fmt::Display for ParseMatchPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown match policy `{}`", self.policy)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UriFromBasicError {
     Parse(ParseUriError),
@@ -58,3 +108,12 @@ impl From<UnexpectedType> for KnownKindFromBasicError {
         Self::UnexpectedType(err)
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Error produced registering a custom kind with a code or name already taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterKindError {
+    pub kind: CustomKind,
+    pub existing: KnownKind,
+}
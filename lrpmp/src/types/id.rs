@@ -26,7 +26,9 @@ impl<M, V> FromBasicValuePart<M, V> for Id {
     type Error = UnexpectedType;
 
     fn expected_types() -> &'static [BasicType] {
-        &[BasicType::Map]
+        // Ids go on the wire as integers, never as maps, and a `u8` is
+        // widened, so small ids read by any decoder are accepted.
+        &[BasicType::U64]
     }
 
     fn from_basic_u64(v: u64) -> Result<Self, Self::Error> {
@@ -39,3 +41,25 @@ impl From<Id> for u64 {
         id.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_from_basic_value() {
+        assert_eq!(
+            <Id as FromBasicValuePart<(), ()>>::expected_types(),
+            &[BasicType::U64]
+        );
+        assert_eq!(
+            <Id as FromBasicValuePart<(), ()>>::from_basic_u64(1),
+            Ok(Id::new(1))
+        );
+        // Small ids decoded as a `u8` are widened.
+        assert_eq!(
+            <Id as FromBasicValuePart<(), ()>>::from_basic_u8(1),
+            Ok(Id::new(1))
+        );
+    }
+}
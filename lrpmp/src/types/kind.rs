@@ -1,7 +1,14 @@
+use std::sync::{PoisonError, RwLock};
+
+use once_cell::sync::Lazy;
+
 use super::*;
 
 pub use crate::std_kind::*;
 
+/// Custom kinds registered with [`CustomKind::register`].
+static CUSTOM_KINDS: Lazy<RwLock<Vec<CustomKind>>> = Lazy::new(Default::default);
+
 /// Represents a message kind (eg, `CALL`, `20`).
 #[derive(Debug, Clone, PartialEq)]
 pub enum Kind {
@@ -111,12 +118,26 @@ impl KnownKind {
         }
     }
 
+    /// Returns the kind name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Standard(k) => k.name(),
+            Self::Custom(k) => k.name(),
+        }
+    }
+
+    /// Looks up a standard or registered custom kind by name.
     pub fn from_name(name: &str) -> Option<Self> {
-        StandardKind::from_name(name).map(Self::Standard)
+        StandardKind::from_name(name)
+            .map(Self::Standard)
+            .or_else(|| CustomKind::from_name(name).map(Self::Custom))
     }
 
+    /// Looks up a standard or registered custom kind by code.
     pub fn from_code(code: u8) -> Option<Self> {
-        StandardKind::from_code(code).map(Self::Standard)
+        StandardKind::from_code(code)
+            .map(Self::Standard)
+            .or_else(|| CustomKind::from_code(code).map(Self::Custom))
     }
 
     /// Returns the lower and upper bound of the number of fields in the message kind.
@@ -126,6 +147,16 @@ impl KnownKind {
             Self::Custom(k) => k.field_count(),
         }
     }
+
+    /// Returns the names of the session stages the message kind is valid in.
+    ///
+    /// Custom kinds are only valid in an established session.
+    pub fn stages(&self) -> &'static [&'static str] {
+        match self {
+            Self::Standard(k) => k.stages(),
+            Self::Custom(_) => &["established"],
+        }
+    }
 }
 
 impl From<CustomKind> for KnownKind {
@@ -203,6 +234,51 @@ impl CustomKind {
     pub fn field_count(&self) -> (usize, Option<usize>) {
         (self.fields_min, self.fields_max)
     }
+
+    /// Registers the kind so it is recognized when decoding messages.
+    ///
+    /// Registering the same kind again does nothing. Fails if the code or
+    /// name is taken by a standard kind or another registered kind.
+    pub fn register(self) -> Result<(), RegisterKindError> {
+        let standard =
+            StandardKind::from_code(self.code).or_else(|| StandardKind::from_name(self.name));
+        if let Some(existing) = standard {
+            return Err(RegisterKindError {
+                kind: self,
+                existing: existing.into(),
+            });
+        }
+        let mut kinds = CUSTOM_KINDS.write().unwrap_or_else(PoisonError::into_inner);
+        match kinds
+            .iter()
+            .find(|k| k.code == self.code || k.name == self.name)
+        {
+            Some(existing) if *existing == self => Ok(()),
+            Some(existing) => Err(RegisterKindError {
+                kind: self,
+                existing: (*existing).into(),
+            }),
+            None => {
+                kinds.push(self);
+                Ok(())
+            }
+        }
+    }
+
+    /// Looks up a registered kind by name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::find(|k| k.name == name)
+    }
+
+    /// Looks up a registered kind by code.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::find(|k| k.code == code)
+    }
+
+    fn find(predicate: impl Fn(&Self) -> bool) -> Option<Self> {
+        let kinds = CUSTOM_KINDS.read().unwrap_or_else(PoisonError::into_inner);
+        kinds.iter().copied().find(predicate)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -218,3 +294,32 @@ impl From<StandardKind> for u8 {
         kind as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_custom_kind() {
+        let kind = CustomKind::new("TEST_REGISTER", 201, 1, Some(2));
+        assert_eq!(KnownKind::from_code(201), None);
+        kind.register().unwrap();
+        kind.register().unwrap();
+        assert_eq!(KnownKind::from_code(201), Some(KnownKind::Custom(kind)));
+        assert_eq!(KnownKind::from_name("TEST_REGISTER"), Some(kind.into()));
+
+        let conflict = CustomKind::new("TEST_CONFLICT", 201, 0, None);
+        assert_eq!(
+            conflict.register(),
+            Err(RegisterKindError {
+                kind: conflict,
+                existing: kind.into(),
+            })
+        );
+        let hello = CustomKind::new("HELLO", 202, 0, None);
+        assert_eq!(
+            hello.register().unwrap_err().existing,
+            StandardKind::Hello.into()
+        );
+    }
+}
@@ -14,6 +14,18 @@ impl<M, V> Meta<M, V> {
             inner: Map::new(map),
         }
     }
+
+    pub fn as_inner(&self) -> &M {
+        self.inner.as_inner()
+    }
+
+    pub fn as_inner_mut(&mut self) -> &mut M {
+        self.inner.as_inner_mut()
+    }
+
+    pub fn into_inner(self) -> M {
+        self.inner.into_inner()
+    }
 }
 
 impl<M, V> Default for Meta<M, V>
@@ -8,6 +8,8 @@ mod id;
 mod kind;
 mod meta;
 mod uri;
+mod uri_intern;
+mod uri_trie;
 
 pub use self::basic::*;
 pub use self::body::*;
@@ -16,3 +18,5 @@ pub use self::id::*;
 pub use self::kind::*;
 pub use self::meta::*;
 pub use self::uri::*;
+pub use self::uri_intern::*;
+pub use self::uri_trie::*;
@@ -1,15 +1,27 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::{self, FromStr};
 
 use bytes::Bytes;
 use bytestring::ByteString;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
 
 use lrpmp_spec::uri::{self, UriParts};
 
 use super::*;
 
 /// Represents a resource unique across all sessions.
-// TODO: Serialize, Deserialize
+///
+/// A URI serializes as a string, and is validated when deserialized. A URI
+/// deserialized by [`Raw::parse`](crate::codec::raw::Raw::parse) shares the
+/// buffer parsed where the encoding lends the string out.
+///
+/// URIs are compared, ordered and hashed by their string. An
+/// [`InternedUri`] compares by pointer instead.
 #[derive(Debug, Clone)]
 pub struct Uri {
     contents: ByteString,
@@ -33,6 +45,86 @@ impl Uri {
         self.parts.wildcard_count
     }
 
+    /// Returns `true` if the URI matches this URI as a pattern, where each
+    /// `*` segment matches exactly one segment of the URI.
+    ///
+    /// A `*` within a segment is not a wildcard, so `a*` only matches `a*`.
+    pub fn matches(&self, uri: &Uri) -> bool {
+        if !self.has_wildcard() {
+            return self == uri;
+        }
+        if self.segment_count() != uri.segment_count() {
+            return false;
+        }
+        self.as_str()
+            .split('.')
+            .zip(uri.as_str().split('.'))
+            .all(|(p, s)| p == "*" || p == s)
+    }
+
+    /// Returns `true` if the URI matches this URI interpreted under the
+    /// given policy.
+    pub fn matches_with(&self, uri: &Uri, policy: MatchPolicy) -> bool {
+        match policy {
+            MatchPolicy::Exact => self == uri,
+            MatchPolicy::Prefix => {
+                let prefix = self.as_str();
+                let prefix = prefix.strip_suffix('.').unwrap_or(prefix);
+                match uri.as_str().strip_prefix(prefix) {
+                    Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with('.'),
+                    None => false,
+                }
+            }
+            MatchPolicy::Wildcard => self.matches(uri),
+        }
+    }
+
+    /// Returns a builder for a URI, appending one segment at a time.
+    pub fn builder() -> UriBuilder {
+        UriBuilder::new()
+    }
+
+    /// Returns an iterator over the segments of the URI.
+    ///
+    /// A trailing `.` doesn't end in an empty segment, so `com.myapp.`
+    /// has the same segments as `com.myapp`.
+    pub fn segments(&self) -> Segments<'_> {
+        Segments(self.as_str().split_terminator('.'))
+    }
+
+    /// Returns the URI without its last segment, or `None` if the URI has
+    /// only one segment.
+    pub fn parent(&self) -> Option<Self> {
+        let end = self.as_str().trim_end_matches('.').rfind('.')?;
+        Some(self.slice(0, end))
+    }
+
+    /// Returns the URI with a segment appended, which is validated.
+    ///
+    /// The segment may be a `*` to derive a pattern, but not contain a `.`.
+    pub fn join(&self, segment: &str) -> Result<Self, UriSegmentError> {
+        Ok(UriBuilder::from(self.clone()).segment(segment)?.build())
+    }
+
+    /// Returns the segments of the URI following the segments of a prefix,
+    /// or `None` if the prefix isn't a prefix of the URI, segment-wise, or
+    /// leaves no segments.
+    ///
+    /// A trailing `.` of the prefix is ignored, as when matching by
+    /// [`MatchPolicy::Prefix`].
+    pub fn strip_prefix(&self, prefix: &Uri) -> Option<Self> {
+        let prefix = prefix.as_str().trim_end_matches('.');
+        if prefix.is_empty() {
+            return Some(self.clone());
+        }
+        let rest = self.as_str().strip_prefix(prefix)?.strip_prefix('.')?;
+        if rest.is_empty() {
+            return None;
+        }
+        let start = self.as_str().len() - rest.len();
+        Some(self.slice(start, self.as_str().len()))
+    }
+
     pub fn from_static(s: &'static str) -> Result<Self, ParseUriError> {
         Self::try_from(Bytes::from_static(s.as_bytes()))
     }
@@ -45,6 +137,26 @@ impl Uri {
         Self::from_parts_unchecked(Bytes::from_static(uri.as_bytes()), parts)
     }
 
+    /// Returns a copy of the URI with contents of its own, rather than
+    /// shared with the buffer it was parsed from.
+    pub(crate) fn detach(&self) -> Self {
+        let contents = Bytes::copy_from_slice(self.as_str().as_bytes());
+        unsafe { Self::from_parts_unchecked(contents, self.parts.clone()) }
+    }
+
+    /// Returns the part of the URI between the offsets, which must fall on
+    /// segment boundaries, sharing its contents.
+    fn slice(&self, start: usize, end: usize) -> Self {
+        let bytes = &self.as_str().as_bytes()[start..end];
+        let count = |c| bytes.iter().filter(|b| **b == c).count() as u8;
+        let parts = UriParts {
+            segment_count: count(uri::SEGMENT),
+            wildcard_count: count(uri::WILDCARD),
+        };
+        let contents = self.contents.get_ref().slice(start..end);
+        unsafe { Self::from_parts_unchecked(contents, parts) }
+    }
+
     const unsafe fn from_parts_unchecked(contents: Bytes, parts: UriParts) -> Self {
         let contents = ByteString::from_bytes_unchecked(contents);
         Uri { contents, parts }
@@ -57,12 +169,193 @@ impl PartialEq for Uri {
     }
 }
 
+impl Eq for Uri {}
+
+impl Hash for Uri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Borrow<str> for Uri {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialOrd for Uri {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Uri {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
 impl fmt::Display for Uri {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
+impl Serialize for Uri {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// An iterator over the segments of a [`Uri`].
+#[derive(Debug, Clone)]
+pub struct Segments<'a>(str::SplitTerminator<'a, char>);
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.0.next()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Segments<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.0.next_back()
+    }
+}
+
+/// Builds a [`Uri`] from segments, validating each as it is appended.
+///
+/// # Example
+/// ```rust
+/// use lrpmp::types::Uri;
+///
+/// let uri = Uri::builder().segment("com")?.segment("myapp")?.build();
+/// assert_eq!(uri.as_str(), "com.myapp");
+/// # Ok::<(), lrpmp::types::UriSegmentError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct UriBuilder {
+    contents: String,
+    parts: UriParts,
+}
+
+impl UriBuilder {
+    pub fn new() -> Self {
+        Self {
+            contents: String::new(),
+            parts: UriParts {
+                segment_count: 0,
+                wildcard_count: 0,
+            },
+        }
+    }
+
+    /// Appends a segment, which may be a `*` but not contain a `.`.
+    pub fn segment(mut self, segment: &str) -> Result<Self, UriSegmentError> {
+        if segment.is_empty() {
+            return Err(UriSegmentError::Empty);
+        }
+        let parse_err = |err: uri::UriValidationError| ParseUriError {
+            invalid: err.invalid,
+            offset: err.offset,
+        };
+        let parts = uri::validate_bytes(segment.as_bytes()).map_err(parse_err)?;
+        if let Some(offset) = segment.find('.') {
+            return Err(ParseUriError {
+                invalid: '.',
+                offset,
+            }
+            .into());
+        }
+        // The URI may already end with a `.` when built from a prefix.
+        let dot = !self.contents.is_empty() && !self.contents.ends_with('.');
+        let segment_count = self.parts.segment_count.checked_add(dot as u8);
+        let wildcard_count = self.parts.wildcard_count.checked_add(parts.wildcard_count);
+        match (segment_count, wildcard_count) {
+            (Some(segment_count), Some(wildcard_count)) => {
+                self.parts = UriParts {
+                    segment_count,
+                    wildcard_count,
+                };
+            }
+            _ => return Err(UriSegmentError::TooLong),
+        }
+        if dot {
+            self.contents.push('.');
+        }
+        self.contents.push_str(segment);
+        Ok(self)
+    }
+
+    pub fn build(self) -> Uri {
+        unsafe { Uri::from_parts_unchecked(Bytes::from(self.contents), self.parts) }
+    }
+}
+
+impl Default for UriBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Continues building from the segments of a URI.
+impl From<Uri> for UriBuilder {
+    fn from(uri: Uri) -> Self {
+        Self {
+            contents: uri.as_str().to_string(),
+            parts: uri.parts,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Uri {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(UriVisitor)
+    }
+}
+
+struct UriVisitor;
+
+impl UriVisitor {
+    fn parse<E: de::Error>(contents: Bytes) -> Result<Uri, E> {
+        Uri::try_from(contents).map_err(E::custom)
+    }
+}
+
+impl<'de> Visitor<'de> for UriVisitor {
+    type Value = Uri;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a uri string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Self::parse(Bytes::copy_from_slice(v.as_bytes()))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        match crate::codec::raw::share_borrowed(v) {
+            Some(contents) => Self::parse(contents),
+            None => self.visit_str(v),
+        }
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Self::parse(Bytes::from(v))
+    }
+
+    // Valid URIs are ASCII, so bytes need no separate UTF-8 check.
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Self::parse(Bytes::copy_from_slice(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Self::parse(Bytes::from(v))
+    }
+}
+
 impl TryFrom<Bytes> for Uri {
     type Error = ParseUriError;
 
@@ -119,4 +412,130 @@ impl<M, V> FromBasicValuePart<M, V> for Uri {
     fn from_basic_str(v: String) -> Result<Self, Self::Error> {
         Ok(Self::try_from(v)?)
     }
+
+    fn from_basic_byte_str(v: ByteString) -> Result<Self, Self::Error> {
+        Ok(Self::try_from(v)?)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// How a registered or subscribed URI is matched against the URI of a
+/// call or publication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchPolicy {
+    /// Matches only the same URI, treating `*` as any other character.
+    Exact,
+    /// Matches the same URI and every URI below it, so `com.myapp` (or
+    /// `com.myapp.`) matches `com.myapp.foo` but not `com.myapplet`.
+    Prefix,
+    /// Matches URIs where each `*` segment matches exactly one segment.
+    Wildcard,
+}
+
+impl MatchPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Prefix => "prefix",
+            Self::Wildcard => "wildcard",
+        }
+    }
+}
+
+/// URIs are matched as wildcards unless asked otherwise, which is the same
+/// as exactly for URIs without a `*` segment.
+impl Default for MatchPolicy {
+    fn default() -> Self {
+        Self::Wildcard
+    }
+}
+
+impl FromStr for MatchPolicy {
+    type Err = ParseMatchPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(Self::Exact),
+            "prefix" => Ok(Self::Prefix),
+            "wildcard" => Ok(Self::Wildcard),
+            _ => Err(ParseMatchPolicyError {
+                policy: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for MatchPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::codec::json;
+
+    #[test]
+    fn test_uri_serde() {
+        let uri: Uri = serde_json::from_str(r#""test.echo""#).unwrap();
+        assert_eq!(uri.as_str(), "test.echo");
+        assert_eq!(serde_json::to_string(&uri).unwrap(), r#""test.echo""#);
+
+        let err = serde_json::from_str::<Uri>(r#""test.Echo""#).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid uri char `E` at offset 5"));
+
+        let src = Bytes::from_static(br#"{"procedure":"test.echo"}"#);
+        let raw = json::Raw::new(src.clone());
+        let map: HashMap<String, Uri> = raw.parse().unwrap();
+        // The URI shares the parsed buffer rather than copying from it.
+        let start = src.as_ptr() as usize;
+        let ptr = map["procedure"].as_str().as_ptr() as usize;
+        assert!(ptr > start && ptr < start + src.len());
+    }
+
+    #[test]
+    fn test_uri_segments() {
+        let root = Uri::builder()
+            .segment("com")
+            .unwrap()
+            .segment("myapp")
+            .unwrap()
+            .build();
+        let echo = root.join("echo").unwrap();
+        assert_eq!(echo.as_str(), "com.myapp.echo");
+        assert_eq!(
+            echo.segments().collect::<Vec<_>>(),
+            ["com", "myapp", "echo"]
+        );
+        assert_eq!(echo.segment_count(), 2);
+
+        assert_eq!(echo.parent(), Some(root.clone()));
+        assert_eq!(root.parent().unwrap().parent(), None);
+        assert_eq!(echo.strip_prefix(&root).unwrap().as_str(), "echo");
+        assert_eq!(echo.strip_prefix(&echo), None);
+        let applet = Uri::from_static("com.myapplet.echo").unwrap();
+        assert_eq!(applet.strip_prefix(&root), None);
+
+        let pattern = Uri::from_static("com.").unwrap().join("*").unwrap();
+        assert_eq!(pattern.as_str(), "com.*");
+        assert!(pattern.has_wildcard());
+        assert!(pattern.matches(&root));
+        assert_eq!(pattern.parent().unwrap().wildcard_count(), 0);
+
+        assert_eq!(root.join(""), Err(UriSegmentError::Empty));
+        assert_eq!(
+            root.join("a.b"),
+            Err(UriSegmentError::Parse(ParseUriError {
+                invalid: '.',
+                offset: 1,
+            }))
+        );
+        assert!(root.join("Echo").is_err());
+    }
 }
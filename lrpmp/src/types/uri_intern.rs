@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, PoisonError, RwLock, Weak};
+
+use once_cell::sync::Lazy;
+
+use super::Uri;
+
+static GLOBAL: Lazy<Arc<UriInterner>> = Lazy::new(Default::default);
+
+type Uris = RwLock<HashMap<Uri, Weak<Interned>>>;
+
+/// A set of URIs, handing out a shared handle for each distinct URI.
+///
+/// Interning a URI copies it out of the buffer it was decoded from the
+/// first time it is seen, so routing state holding onto the URI doesn't
+/// keep whole messages alive, and every later copy shares the same
+/// contents. A URI is kept only while a handle to it lives, and is
+/// forgotten when the last one is dropped.
+#[derive(Default)]
+pub struct UriInterner {
+    uris: Arc<Uris>,
+}
+
+impl UriInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the interner shared by the whole process.
+    pub fn global() -> Arc<Self> {
+        GLOBAL.clone()
+    }
+
+    /// Returns the handle for a URI, interning it if not seen before.
+    pub fn intern(&self, uri: &Uri) -> InternedUri {
+        if let Some(interned) = self.get(uri) {
+            return interned;
+        }
+        let mut uris = self.uris.write().unwrap();
+        // Another thread may have interned the URI between the locks.
+        if let Some(inner) = uris.get(uri.as_str()).and_then(Weak::upgrade) {
+            return InternedUri { inner };
+        }
+        let interned = InternedUri::new(uri.detach(), Arc::downgrade(&self.uris));
+        uris.insert(interned.to_uri(), Arc::downgrade(&interned.inner));
+        interned
+    }
+
+    /// Returns the handle for a URI if interned and still alive.
+    pub fn get(&self, uri: &Uri) -> Option<InternedUri> {
+        let uris = self.uris.read().unwrap();
+        let inner = uris.get(uri.as_str()).and_then(Weak::upgrade)?;
+        Some(InternedUri { inner })
+    }
+
+    /// Returns the number of distinct URIs interned.
+    pub fn len(&self) -> usize {
+        self.uris.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for UriInterner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UriInterner")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A handle to a URI interned by a [`UriInterner`], which keeps the URI
+/// interned while alive.
+///
+/// Handles are cheap to clone and hash with a hash computed once when
+/// interned. Handles from the same interner are equal only if they are the
+/// same handle; handles from different interners fall back to comparing
+/// their URIs. A handle derefs to its [`Uri`], and any `Uri` taken from it
+/// shares its contents.
+#[derive(Clone)]
+pub struct InternedUri {
+    inner: Arc<Interned>,
+}
+
+struct Interned {
+    uri: Uri,
+    hash: u64,
+    uris: Weak<Uris>,
+}
+
+impl Drop for Interned {
+    fn drop(&mut self) {
+        let uris = match self.uris.upgrade() {
+            Some(uris) => uris,
+            None => return,
+        };
+        let mut uris = uris.write().unwrap_or_else(PoisonError::into_inner);
+        // The URI may have been interned again since the last handle was
+        // dropped, under a new entry to be kept.
+        let dead = match uris.get(self.uri.as_str()) {
+            Some(entry) => entry.strong_count() == 0,
+            None => false,
+        };
+        if dead {
+            uris.remove(self.uri.as_str());
+        }
+    }
+}
+
+impl InternedUri {
+    fn new(uri: Uri, uris: Weak<Uris>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        uri.hash(&mut hasher);
+        let hash = hasher.finish();
+        Self {
+            inner: Arc::new(Interned { uri, hash, uris }),
+        }
+    }
+
+    pub fn as_uri(&self) -> &Uri {
+        &self.inner.uri
+    }
+
+    pub fn to_uri(&self) -> Uri {
+        self.inner.uri.clone()
+    }
+}
+
+impl Deref for InternedUri {
+    type Target = Uri;
+
+    fn deref(&self) -> &Uri {
+        self.as_uri()
+    }
+}
+
+impl AsRef<Uri> for InternedUri {
+    fn as_ref(&self) -> &Uri {
+        self.as_uri()
+    }
+}
+
+impl From<InternedUri> for Uri {
+    fn from(interned: InternedUri) -> Self {
+        interned.to_uri()
+    }
+}
+
+impl PartialEq for InternedUri {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+            || (self.inner.hash == other.inner.hash && self.inner.uri == other.inner.uri)
+    }
+}
+
+impl Eq for InternedUri {}
+
+impl PartialEq<Uri> for InternedUri {
+    fn eq(&self, other: &Uri) -> bool {
+        self.as_uri() == other
+    }
+}
+
+impl Hash for InternedUri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.inner.hash)
+    }
+}
+
+impl PartialOrd for InternedUri {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InternedUri {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return Ordering::Equal;
+        }
+        self.as_uri().cmp(other.as_uri())
+    }
+}
+
+impl fmt::Debug for InternedUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_uri(), f)
+    }
+}
+
+impl fmt::Display for InternedUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_uri(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+    use std::convert::TryFrom;
+
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_uri_interner() {
+        let src = Bytes::from_static(b"[\"test.echo\"]");
+        let decoded = Uri::try_from(src.slice(2..11)).unwrap();
+
+        let interner = UriInterner::new();
+        let a = interner.intern(&decoded);
+        let b = interner.intern(&Uri::try_from("test.echo".to_string()).unwrap());
+        assert!(Arc::ptr_eq(&a.inner, &b.inner));
+        assert_eq!(interner.len(), 1);
+        // The interned URI no longer shares the decoded buffer.
+        assert_ne!(a.as_str().as_ptr(), decoded.as_str().as_ptr());
+        assert_eq!(a.to_uri().as_str().as_ptr(), b.as_str().as_ptr());
+
+        // Handles from another interner compare by their URI.
+        let other = UriInterner::new().intern(&decoded);
+        assert_eq!(a, other);
+        let set: HashSet<_> = vec![a.clone(), other].into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        let mut map = BTreeMap::new();
+        map.insert(Uri::try_from("test.z".to_string()).unwrap(), 2);
+        map.insert(a.to_uri(), 1);
+        assert_eq!(map.get(&decoded), Some(&1));
+        assert_eq!(map.keys().next(), Some(a.as_uri()));
+
+        // The URI is forgotten with its last handle.
+        drop(a);
+        drop(set);
+        assert!(interner.get(&decoded).is_some());
+        drop(b);
+        assert!(interner.get(&decoded).is_none());
+        assert!(interner.is_empty());
+    }
+}
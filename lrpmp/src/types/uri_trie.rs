@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::slice;
+
+use super::{MatchPolicy, Uri};
+
+const WILDCARD: &str = "*";
+
+/// A map from URI patterns to values, which finds every pattern matching a
+/// URI by walking its segments rather than testing each pattern.
+///
+/// Patterns are keyed by their URI and [`MatchPolicy`]. A pattern may hold
+/// any number of values, kept in insertion order.
+#[derive(Debug, Clone)]
+pub struct UriTrie<T> {
+    root: Node<T>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    children: HashMap<Box<str>, Node<T>>,
+    wildcard: Option<Box<Node<T>>>,
+    /// The patterns ending at this node, indexed by policy.
+    entries: [Entry<T>; 3],
+}
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    pattern: Option<Uri>,
+    values: Vec<T>,
+}
+
+impl<T> UriTrie<T> {
+    pub fn new() -> Self {
+        Self {
+            root: Node::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of values in the trie.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Adds a value for a pattern.
+    pub fn insert(&mut self, pattern: Uri, policy: MatchPolicy, value: T) {
+        let mut node = &mut self.root;
+        for segment in segments(&pattern, policy) {
+            node = node.child_mut(segment, policy);
+        }
+        let entry = &mut node.entries[index(policy)];
+        entry.pattern.get_or_insert(pattern);
+        entry.values.push(value);
+        self.len += 1;
+    }
+
+    /// Returns the values inserted for exactly this pattern.
+    pub fn get(&self, pattern: &Uri, policy: MatchPolicy) -> &[T] {
+        let mut node = &self.root;
+        for segment in segments(pattern, policy) {
+            node = match node.child(segment, policy) {
+                Some(child) => child,
+                None => return &[],
+            };
+        }
+        &node.entry(policy).values
+    }
+
+    /// Removes every value inserted for exactly this pattern.
+    pub fn remove(&mut self, pattern: &Uri, policy: MatchPolicy) -> Vec<T> {
+        let mut removed = Vec::new();
+        let segments = segments(pattern, policy);
+        self.root
+            .remove(&segments, policy, &mut |_| true, &mut removed);
+        self.len -= removed.len();
+        removed
+    }
+
+    /// Removes the first value inserted for exactly this pattern for which
+    /// the predicate returns `true`.
+    pub fn remove_if<F>(
+        &mut self,
+        pattern: &Uri,
+        policy: MatchPolicy,
+        mut predicate: F,
+    ) -> Option<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut found = false;
+        let mut f = |value: &T| {
+            let remove = !found && predicate(value);
+            found |= remove;
+            remove
+        };
+        let segments = segments(pattern, policy);
+        self.root.remove(&segments, policy, &mut f, &mut removed);
+        self.len -= removed.len();
+        removed.pop()
+    }
+
+    /// Retains only the values for which the predicate returns `true`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.root.retain(&mut f);
+        self.len = self.root.count();
+    }
+
+    /// Returns every pattern matching the URI along with its values.
+    ///
+    /// Patterns are yielded in order of precedence:
+    ///
+    /// 1. Exact patterns, then wildcard patterns without a `*` segment.
+    /// 2. Prefix patterns, longest first.
+    /// 3. Wildcard patterns, most specific first: at the first segment two
+    ///    patterns differ in, the pattern with a literal segment is yielded
+    ///    before the pattern with a `*`.
+    pub fn matches<'a>(&'a self, uri: &'a Uri) -> Matches<'a, T> {
+        let segments: Vec<_> = uri.as_str().split('.').collect();
+        let mut path = vec![&self.root];
+        for segment in segments.iter() {
+            match path.last().unwrap().children.get(*segment) {
+                Some(child) => path.push(child),
+                None => break,
+            }
+        }
+        // Kept in reverse, to be popped in order of precedence.
+        let mut entries: Vec<_> = path
+            .iter()
+            .map(|node| node.entry(MatchPolicy::Prefix))
+            .collect();
+        if path.len() > segments.len() {
+            let node = path[segments.len()];
+            entries.push(node.entry(MatchPolicy::Wildcard));
+            entries.push(node.entry(MatchPolicy::Exact));
+        }
+        Matches {
+            segments,
+            entries,
+            stack: vec![(&self.root, 0, false)],
+            current: None,
+        }
+    }
+}
+
+impl<T> Default for UriTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            children: HashMap::new(),
+            wildcard: None,
+            entries: [Entry::new(), Entry::new(), Entry::new()],
+        }
+    }
+
+    fn entry(&self, policy: MatchPolicy) -> &Entry<T> {
+        &self.entries[index(policy)]
+    }
+
+    fn child(&self, segment: &str, policy: MatchPolicy) -> Option<&Self> {
+        if is_wildcard(segment, policy) {
+            self.wildcard.as_deref()
+        } else {
+            self.children.get(segment)
+        }
+    }
+
+    fn child_mut(&mut self, segment: &str, policy: MatchPolicy) -> &mut Self {
+        if is_wildcard(segment, policy) {
+            self.wildcard.get_or_insert_with(|| Box::new(Self::new()))
+        } else {
+            self.children
+                .entry(segment.into())
+                .or_insert_with(Self::new)
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.iter().all(|e| e.values.is_empty())
+            && self.children.is_empty()
+            && self.wildcard.is_none()
+    }
+
+    fn count(&self) -> usize {
+        let values: usize = self.entries.iter().map(|e| e.values.len()).sum();
+        let children: usize = self.children.values().map(Self::count).sum();
+        let wildcard = self.wildcard.as_ref().map_or(0, |w| w.count());
+        values + children + wildcard
+    }
+
+    fn remove<F>(&mut self, segments: &[&str], policy: MatchPolicy, f: &mut F, removed: &mut Vec<T>)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => {
+                self.entries[index(policy)].remove(f, removed);
+                return;
+            }
+        };
+        if is_wildcard(segment, policy) {
+            if let Some(wildcard) = &mut self.wildcard {
+                wildcard.remove(rest, policy, f, removed);
+                if wildcard.is_empty() {
+                    self.wildcard = None;
+                }
+            }
+        } else if let Some(child) = self.children.get_mut(*segment) {
+            child.remove(rest, policy, f, removed);
+            if child.is_empty() {
+                self.children.remove(*segment);
+            }
+        }
+    }
+
+    fn retain<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        for entry in self.entries.iter_mut() {
+            entry.values.retain(|value| f(value));
+            if entry.values.is_empty() {
+                entry.pattern = None;
+            }
+        }
+        self.children.retain(|_, child| {
+            child.retain(f);
+            !child.is_empty()
+        });
+        if let Some(wildcard) = &mut self.wildcard {
+            wildcard.retain(f);
+            if wildcard.is_empty() {
+                self.wildcard = None;
+            }
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    fn new() -> Self {
+        Self {
+            pattern: None,
+            values: Vec::new(),
+        }
+    }
+
+    fn remove<F>(&mut self, f: &mut F, removed: &mut Vec<T>)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut i = 0;
+        while i < self.values.len() {
+            if f(&self.values[i]) {
+                removed.push(self.values.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        if self.values.is_empty() {
+            self.pattern = None;
+        }
+    }
+}
+
+fn index(policy: MatchPolicy) -> usize {
+    match policy {
+        MatchPolicy::Exact => 0,
+        MatchPolicy::Prefix => 1,
+        MatchPolicy::Wildcard => 2,
+    }
+}
+
+fn is_wildcard(segment: &str, policy: MatchPolicy) -> bool {
+    policy == MatchPolicy::Wildcard && segment == WILDCARD
+}
+
+/// Returns the segments of a pattern, less the trailing `.` of a prefix.
+fn segments(pattern: &Uri, policy: MatchPolicy) -> Vec<&str> {
+    let mut pattern = pattern.as_str();
+    if policy == MatchPolicy::Prefix {
+        pattern = pattern.strip_suffix('.').unwrap_or(pattern);
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+    }
+    pattern.split('.').collect()
+}
+
+/// An iterator over the patterns matching a URI, returned by
+/// [`UriTrie::matches`].
+pub struct Matches<'a, T> {
+    segments: Vec<&'a str>,
+    /// The exact and prefix entries matched, in reverse precedence.
+    entries: Vec<&'a Entry<T>>,
+    /// The nodes left to visit for wildcard patterns, with their depth and
+    /// whether a `*` was followed to reach them.
+    stack: Vec<(&'a Node<T>, usize, bool)>,
+    current: Option<(&'a Uri, slice::Iter<'a, T>)>,
+}
+
+impl<'a, T> Matches<'a, T> {
+    fn next_entry(&mut self) -> Option<&'a Entry<T>> {
+        if let Some(entry) = self.entries.pop() {
+            return Some(entry);
+        }
+        loop {
+            let (node, depth, wildcard) = self.stack.pop()?;
+            let segment = match self.segments.get(depth) {
+                Some(segment) => segment,
+                // Wildcard patterns without a `*` were matched as exact.
+                None if wildcard => return Some(node.entry(MatchPolicy::Wildcard)),
+                None => continue,
+            };
+            // The literal child is pushed last so it is visited first.
+            if let Some(child) = &node.wildcard {
+                self.stack.push((child, depth + 1, true));
+            }
+            if let Some(child) = node.children.get(*segment) {
+                self.stack.push((child, depth + 1, wildcard));
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for Matches<'a, T> {
+    type Item = (&'a Uri, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((pattern, values)) = &mut self.current {
+                if let Some(value) = values.next() {
+                    return Some((pattern, value));
+                }
+                self.current = None;
+            }
+            let entry = self.next_entry()?;
+            if let Some(pattern) = &entry.pattern {
+                self.current = Some((pattern, entry.values.iter()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(s: &'static str) -> Uri {
+        Uri::from_static(s).unwrap()
+    }
+
+    #[test]
+    fn test_uri_trie_matches() {
+        use MatchPolicy::*;
+
+        let mut trie = UriTrie::new();
+        trie.insert(uri("a.*.c"), Wildcard, 1);
+        trie.insert(uri("*.b.c"), Wildcard, 2);
+        trie.insert(uri("a.b.c"), Wildcard, 3);
+        trie.insert(uri("a.b.*"), Wildcard, 4);
+        trie.insert(uri("a.b"), Wildcard, 5);
+        trie.insert(uri("a.b.c"), Exact, 6);
+        trie.insert(uri("a."), Prefix, 7);
+        trie.insert(uri("a.b"), Prefix, 8);
+        trie.insert(uri("a.b.*"), Exact, 9);
+        assert_eq!(trie.len(), 9);
+
+        let target = uri("a.b.c");
+        let matches: Vec<_> = trie.matches(&target).map(|(_, v)| *v).collect();
+        assert_eq!(matches, vec![6, 3, 8, 7, 4, 1, 2]);
+        assert_eq!(trie.matches(&uri("x.b.c")).count(), 1);
+        assert_eq!(trie.matches(&uri("a.b.c.d")).count(), 2);
+        let matches: Vec<_> = trie.matches(&uri("a.b.*")).map(|(_, v)| *v).collect();
+        assert_eq!(matches, vec![9, 8, 7, 4]);
+        for (policy, value) in [(Exact, 6), (Prefix, 8), (Wildcard, 4)].iter() {
+            let (pattern, _) = trie.matches(&target).find(|(_, v)| *v == value).unwrap();
+            assert!(pattern.matches_with(&target, *policy));
+        }
+
+        assert_eq!(trie.get(&uri("a.b.c"), Wildcard), &[3]);
+        assert_eq!(trie.remove_if(&uri("a.b.c"), Exact, |v| *v == 6), Some(6));
+        assert_eq!(trie.remove(&uri("a.*.c"), Wildcard), vec![1]);
+        trie.retain(|v| *v != 2);
+        let matches: Vec<_> = trie.matches(&target).map(|(_, v)| *v).collect();
+        assert_eq!(matches, vec![3, 8, 7, 4]);
+        assert_eq!(trie.len(), 6);
+    }
+}